@@ -0,0 +1,609 @@
+//! A builder for constructing [`index`] configs in Rust, which checks the bounds of every state
+//! and the validity of every transition when the config is built.
+//!
+//! ```
+//! use nova_software_common::builder::{ConfigBuilder, StateBuilder, Transition};
+//! use nova_software_common::{CheckData, CommandObject, NativeFlagCondition, Seconds};
+//!
+//! let config = ConfigBuilder::new(0)
+//!     .state(StateBuilder::new(0).check(
+//!         CheckData::ApogeeFlag(NativeFlagCondition(true)),
+//!         Some(Transition::To(1)),
+//!     ))
+//!     .state(
+//!         StateBuilder::new(1)
+//!             .command(CommandObject::Pyro1(true), Seconds(0.0))
+//!             .terminal(),
+//!     )
+//!     .build()
+//!     .unwrap();
+//!
+//! assert_eq!(config.states.len(), 2);
+//! ```
+
+use crate::index::{self, StateIndex};
+use crate::{AbortReason, CheckData, CommandObject, Seconds, MAX_STATES};
+
+use heapless::Vec;
+
+/// An error in a config found while building it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// More than [`MAX_STATES`] states were added
+    TooManyStates,
+    /// More than [`crate::MAX_CHECKS_PER_STATE`] checks were added to the state `state`
+    TooManyChecks { state: u8 },
+    /// More than [`crate::MAX_COMMANDS_PER_STATE`] commands were added to the state `state`
+    TooManyCommands { state: u8 },
+    /// Two states were given the same id
+    DuplicateState { state: u8 },
+    /// State ids must be contiguous from zero, but there is no state with this id
+    MissingState { state: u8 },
+    /// A transition or the default state refers to a state id that was never added
+    UnknownState { state: u8 },
+    /// A `DataRate` command in the state `state` samples at `rate` Hz, which is outside of
+    /// [`crate::MIN_DATA_RATE_HZ`] to [`crate::MAX_DATA_RATE_HZ`]
+    UnsupportedDataRate { state: u8, rate: u16 },
+    /// The name given to the state `state` is longer than [`crate::MAX_STATE_NAME_LEN`]
+    NameTooLong { state: u8 },
+    /// A periodic command in the state `state` has an interval that is not a positive number of
+    /// seconds
+    InvalidInterval { state: u8 },
+    /// A group of checks with no conditions was added to the state `state`, so its transition
+    /// could never be made
+    EmptyGroup { state: u8 },
+}
+
+/// A transition to another state by its id, which is checked when the config is built
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Transition {
+    /// A safe transition to the state with this id
+    To(u8),
+    /// An abort to the state with this id
+    Abort(u8, AbortReason),
+}
+
+impl Transition {
+    fn into_index(self) -> index::StateTransition {
+        // # SAFETY: Every id used by a transition is checked to be in bounds in
+        // `ConfigBuilder::build` before the config is returned
+        match self {
+            Transition::To(id) => {
+                index::StateTransition::Transition(unsafe { StateIndex::new_unchecked(id) })
+            }
+            Transition::Abort(id, reason) => {
+                index::StateTransition::Abort(unsafe { StateIndex::new_unchecked(id) }, reason)
+            }
+        }
+    }
+}
+
+/// Builds a single [`index::State`]
+pub struct StateBuilder {
+    id: u8,
+    state: index::State,
+    next_group: u8,
+    error: Option<BuildError>,
+}
+
+impl StateBuilder {
+    /// Starts building the state with `id`, which is its index in the built config
+    pub fn new(id: u8) -> Self {
+        Self {
+            id,
+            state: index::State::new(Vec::new(), Vec::new(), None),
+            next_group: 0,
+            error: None,
+        }
+    }
+
+    /// Adds a check, which makes `transition` when `data` is satisfied
+    pub fn check(self, data: CheckData, transition: Option<Transition>) -> Self {
+        self.push_check(data, transition, None)
+    }
+
+    /// Adds a check with `priority`, which is evaluated before any check with a lower priority. See
+    /// [`index::Check::priority`]
+    pub fn check_with_priority(
+        mut self,
+        data: CheckData,
+        transition: Option<Transition>,
+        priority: u8,
+    ) -> Self {
+        let len = self.state.checks.len();
+        self = self.push_check(data, transition, None);
+        if self.state.checks.len() > len {
+            self.state.checks[len].priority = priority;
+        }
+        self
+    }
+
+    /// Adds a group of checks, which only makes `transition` once every condition is satisfied at
+    /// the same time
+    pub fn all_of(mut self, conditions: &[CheckData], transition: Transition) -> Self {
+        if conditions.is_empty() {
+            self.set_error(BuildError::EmptyGroup { state: self.id });
+            return self;
+        }
+
+        let group = self.next_group;
+        match self.next_group.checked_add(1) {
+            Some(next_group) => self.next_group = next_group,
+            // Every group has a check, so there are already more checks than a state can hold
+            None => self.set_error(BuildError::TooManyChecks { state: self.id }),
+        }
+
+        for (i, data) in conditions.iter().enumerate() {
+            let transition = if i == 0 { Some(transition) } else { None };
+            self = self.push_check(*data, transition, Some(group));
+        }
+        self
+    }
+
+    /// Adds a command, which sets `object` `delay` seconds after the state is entered
+    pub fn command(self, object: CommandObject, delay: Seconds) -> Self {
+        self.push_command(index::Command::new(object, delay))
+    }
+
+    /// Adds a pyro command, which turns `object` on `delay` seconds after the state is entered and
+    /// automatically turns it off again after `pulse`
+    pub fn pulse(self, object: CommandObject, delay: Seconds, pulse: Seconds) -> Self {
+        let mut command = index::Command::new(object, delay);
+        command.pulse = Some(pulse);
+        self.push_command(command)
+    }
+
+    /// Adds a periodic command, which sets `object` every `interval` while the state is active,
+    /// starting `interval` after the state is entered
+    pub fn periodic(self, object: CommandObject, interval: Seconds) -> Self {
+        let mut command = index::Command::new(object, interval);
+        command.repeat = crate::Repeat::Periodic;
+        self.push_command(command)
+    }
+
+    /// Makes `transition` once the state has been active for `time` seconds
+    pub fn timeout(mut self, time: f32, transition: Transition) -> Self {
+        self.state.timeout = Some(index::Timeout::new(time, transition.into_index()));
+        self
+    }
+
+    /// Makes `transition` when a check reads an invalid sensor value
+    pub fn fault_transition(mut self, transition: Transition) -> Self {
+        self.state.fault_transition = Some(transition.into_index());
+        self
+    }
+
+    /// Prevents any transition until the state has been active for `min_dwell`
+    pub fn min_dwell(mut self, min_dwell: Seconds) -> Self {
+        self.state.min_dwell = min_dwell;
+        self
+    }
+
+    /// Names the state, so it can be recognized in logs
+    pub fn name(mut self, name: &str) -> Self {
+        let mut state_name = crate::StateName::new();
+        if state_name.push_str(name).is_err() {
+            self.set_error(BuildError::NameTooLong { state: self.id });
+        }
+        self.state.name = Some(state_name);
+        self
+    }
+
+    /// Marks the state as a deliberate final state
+    pub fn terminal(mut self) -> Self {
+        self.state.is_terminal = true;
+        self
+    }
+
+    fn push_check(
+        mut self,
+        data: CheckData,
+        transition: Option<Transition>,
+        group: Option<u8>,
+    ) -> Self {
+        let mut check = index::Check::new(data, transition.map(Transition::into_index));
+        check.group = group;
+
+        if self.state.checks.push(check).is_err() {
+            self.set_error(BuildError::TooManyChecks { state: self.id });
+        }
+        self
+    }
+
+    fn push_command(mut self, command: index::Command) -> Self {
+        if !command.has_valid_interval() {
+            self.set_error(BuildError::InvalidInterval { state: self.id });
+        }
+        if let CommandObject::DataRate(rate) = command.object {
+            if !command.object.is_supported() {
+                self.set_error(BuildError::UnsupportedDataRate {
+                    state: self.id,
+                    rate,
+                });
+            }
+        }
+
+        if self.state.commands.push(command).is_err() {
+            self.set_error(BuildError::TooManyCommands { state: self.id });
+        }
+        self
+    }
+
+    /// Records `error`, keeping the first error encountered so it is the one reported
+    fn set_error(&mut self, error: BuildError) {
+        self.error.get_or_insert(error);
+    }
+}
+
+/// Builds an [`index::ConfigFile`] out of [`StateBuilder`]s
+pub struct ConfigBuilder {
+    default_state: u8,
+    states: Vec<StateBuilder, MAX_STATES>,
+    continuity_guard: Option<(Seconds, u8)>,
+    error: Option<BuildError>,
+}
+
+impl ConfigBuilder {
+    /// Starts building a config which begins in the state with id `default_state`
+    pub fn new(default_state: u8) -> Self {
+        Self {
+            default_state,
+            states: Vec::new(),
+            continuity_guard: None,
+            error: None,
+        }
+    }
+
+    /// Makes every pyro command wait for continuity on its channel before firing, aborting to the
+    /// state with id `abort_to` if it has waited `timeout` since it was due. See
+    /// [`index::ContinuityGuard`]
+    pub fn continuity_guard(mut self, timeout: Seconds, abort_to: u8) -> Self {
+        self.continuity_guard = Some((timeout, abort_to));
+        self
+    }
+
+    /// Adds a state to the config
+    pub fn state(mut self, state: StateBuilder) -> Self {
+        if self.states.push(state).is_err() {
+            self.error.get_or_insert(BuildError::TooManyStates);
+        }
+        self
+    }
+
+    /// Builds the config, returning the first error found in it
+    pub fn build(self) -> Result<index::ConfigFile, BuildError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if let Some(error) = self.states.iter().find_map(|state| state.error) {
+            return Err(error);
+        }
+
+        let mut states = self.states;
+        states.sort_unstable_by_key(|state| state.id);
+        for (i, state) in states.iter().enumerate() {
+            match (state.id as usize).cmp(&i) {
+                core::cmp::Ordering::Less => {
+                    return Err(BuildError::DuplicateState { state: state.id })
+                }
+                core::cmp::Ordering::Greater => {
+                    return Err(BuildError::MissingState { state: i as u8 })
+                }
+                core::cmp::Ordering::Equal => {}
+            }
+        }
+
+        let len = states.len();
+        let check = |id: usize| {
+            if id < len {
+                Ok(())
+            } else {
+                Err(BuildError::UnknownState { state: id as u8 })
+            }
+        };
+
+        check(self.default_state as usize)?;
+        if let Some((_, abort_to)) = self.continuity_guard {
+            check(abort_to as usize)?;
+        }
+        for state in states.iter() {
+            for transition in state.state.transitions() {
+                check(usize::from(transition.state()))?;
+            }
+        }
+
+        Ok(index::ConfigFile {
+            // # SAFETY: `default_state` was just checked to be in bounds
+            default_state: unsafe { StateIndex::new_unchecked(self.default_state) },
+            states: states.into_iter().map(|state| state.state).collect(),
+            continuity_guard: self.continuity_guard.map(|(timeout, abort_to)| {
+                index::ContinuityGuard {
+                    timeout,
+                    // # SAFETY: `abort_to` was just checked to be in bounds
+                    abort_to: unsafe { StateIndex::new_unchecked(abort_to) },
+                }
+            }),
+        })
+    }
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BuildError, ConfigBuilder, StateBuilder, Transition};
+    use crate::index::{StateIndex, StateTransition};
+    use crate::{
+        AbortReason, CheckData, CommandObject, FloatCondition, NativeFlagCondition,
+        PyroContinuityCondition, Seconds,
+    };
+    use core::fmt::Write;
+
+    const POWERON: u8 = 0;
+    const LAUNCH: u8 = 1;
+    const FLIGHT: u8 = 2;
+    const DROGUE: u8 = 3;
+    const MAIN: u8 = 4;
+    const SAFE: u8 = 5;
+
+    #[test]
+    fn test_dual_deploy() {
+        let config = ConfigBuilder::new(POWERON)
+            .state(
+                StateBuilder::new(POWERON)
+                    .check(
+                        CheckData::Pyro1Continuity(PyroContinuityCondition(false)),
+                        Some(Transition::Abort(SAFE, AbortReason::ContinuityLoss)),
+                    )
+                    .check(
+                        CheckData::Pyro2Continuity(PyroContinuityCondition(false)),
+                        Some(Transition::Abort(SAFE, AbortReason::ContinuityLoss)),
+                    )
+                    .timeout(1.0, Transition::To(LAUNCH)),
+            )
+            .state(StateBuilder::new(LAUNCH).check(
+                CheckData::Altitude(FloatCondition::GreaterThan(200.0)),
+                Some(Transition::To(FLIGHT)),
+            ))
+            .state(StateBuilder::new(FLIGHT).check(
+                CheckData::ApogeeFlag(NativeFlagCondition(true)),
+                Some(Transition::To(DROGUE)),
+            ))
+            .state(
+                StateBuilder::new(DROGUE)
+                    .command(CommandObject::Pyro1(true), Seconds(0.0))
+                    .all_of(
+                        &[
+                            CheckData::Altitude(FloatCondition::LessThan(300.0)),
+                            CheckData::Velocity(FloatCondition::LessThan(0.0)),
+                        ],
+                        Transition::To(MAIN),
+                    ),
+            )
+            .state(
+                StateBuilder::new(MAIN)
+                    .pulse(CommandObject::Pyro2(true), Seconds(0.0), Seconds(1.0))
+                    .terminal(),
+            )
+            .state(StateBuilder::new(SAFE).terminal())
+            .build()
+            .unwrap();
+
+        assert_eq!(usize::from(config.default_state), POWERON as usize);
+        assert_eq!(config.states.len(), 6);
+
+        let poweron = &config.states[POWERON as usize];
+        assert_eq!(poweron.checks.len(), 2);
+        assert!(matches!(
+            poweron.timeout.unwrap().transition,
+            StateTransition::Transition(s) if usize::from(s) == LAUNCH as usize
+        ));
+
+        let drogue = &config.states[DROGUE as usize];
+        assert_eq!(drogue.commands[0].object, CommandObject::Pyro1(true));
+        assert_eq!(drogue.checks.len(), 2);
+        assert!(drogue.checks.iter().all(|check| check.group == Some(0)));
+        assert!(drogue.checks[0].transition.is_some());
+        assert!(drogue.checks[1].transition.is_none());
+        assert_eq!(drogue.commands[0].pulse, None);
+
+        let main = &config.states[MAIN as usize];
+        assert_eq!(main.commands[0].pulse, Some(Seconds(1.0)));
+
+        assert!(crate::validate(&config).is_ok());
+    }
+
+    #[test]
+    fn test_too_many_checks() {
+        let check = CheckData::ApogeeFlag(NativeFlagCondition(true));
+        let state = StateBuilder::new(0)
+            .check(check, None)
+            .check(check, None)
+            .check(check, None)
+            .check(check, None);
+
+        assert_eq!(
+            ConfigBuilder::new(0).state(state).build(),
+            Err(BuildError::TooManyChecks { state: 0 })
+        );
+    }
+
+    #[test]
+    fn test_too_many_commands() {
+        let state = StateBuilder::new(0)
+            .command(CommandObject::Beacon(true), Seconds(0.0))
+            .command(CommandObject::Beacon(false), Seconds(1.0))
+            .command(CommandObject::Beacon(true), Seconds(2.0))
+            .command(CommandObject::Beacon(false), Seconds(3.0));
+
+        assert_eq!(
+            ConfigBuilder::new(0).state(state).build(),
+            Err(BuildError::TooManyCommands { state: 0 })
+        );
+    }
+
+    #[test]
+    fn test_too_many_states() {
+        let config = (0..=crate::MAX_STATES as u8).fold(ConfigBuilder::new(0), |config, id| {
+            config.state(StateBuilder::new(id))
+        });
+
+        assert_eq!(config.build(), Err(BuildError::TooManyStates));
+    }
+
+    #[test]
+    fn test_invalid_state_ids() {
+        assert_eq!(
+            ConfigBuilder::new(0)
+                .state(StateBuilder::new(0).timeout(1.0, Transition::To(7)))
+                .build(),
+            Err(BuildError::UnknownState { state: 7 })
+        );
+        assert_eq!(
+            ConfigBuilder::new(0)
+                .state(StateBuilder::new(0))
+                .state(StateBuilder::new(0))
+                .build(),
+            Err(BuildError::DuplicateState { state: 0 })
+        );
+        assert_eq!(
+            ConfigBuilder::new(0)
+                .state(StateBuilder::new(0))
+                .state(StateBuilder::new(2))
+                .build(),
+            Err(BuildError::MissingState { state: 1 })
+        );
+    }
+
+    #[test]
+    fn test_empty_group() {
+        let config = ConfigBuilder::new(0)
+            .state(StateBuilder::new(0).all_of(&[], Transition::To(1)).check(
+                CheckData::ApogeeFlag(NativeFlagCondition(true)),
+                Some(Transition::To(1)),
+            ))
+            .state(StateBuilder::new(1).terminal())
+            .build();
+        assert_eq!(config, Err(BuildError::EmptyGroup { state: 0 }));
+    }
+
+    #[test]
+    fn test_invalid_interval() {
+        let build = |interval| {
+            ConfigBuilder::new(0)
+                .state(
+                    StateBuilder::new(0)
+                        .periodic(CommandObject::Beacon(true), Seconds(interval))
+                        .terminal(),
+                )
+                .build()
+        };
+
+        for interval in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert_eq!(
+                build(interval),
+                Err(BuildError::InvalidInterval { state: 0 })
+            );
+        }
+        assert!(build(0.5).is_ok());
+    }
+
+    #[test]
+    fn test_unsupported_data_rate() {
+        let build = |rate| {
+            ConfigBuilder::new(0)
+                .state(
+                    StateBuilder::new(0)
+                        .command(CommandObject::DataRate(rate), Seconds(0.0))
+                        .terminal(),
+                )
+                .build()
+        };
+
+        for rate in [0, crate::MIN_DATA_RATE_HZ - 1, crate::MAX_DATA_RATE_HZ + 1] {
+            assert_eq!(
+                build(rate),
+                Err(BuildError::UnsupportedDataRate { state: 0, rate })
+            );
+        }
+        assert!(build(crate::MIN_DATA_RATE_HZ).is_ok());
+        assert!(build(crate::MAX_DATA_RATE_HZ).is_ok());
+    }
+
+    #[test]
+    fn test_altitude_in_feet() {
+        let config = ConfigBuilder::new(0)
+            .state(
+                StateBuilder::new(0)
+                    .check(
+                        CheckData::altitude_in_feet(FloatCondition::LessThan(1000.0)),
+                        Some(Transition::To(1)),
+                    )
+                    .check(
+                        CheckData::altitude_in_feet(FloatCondition::between(500.0, 2500.0)),
+                        None,
+                    ),
+            )
+            .state(StateBuilder::new(1).terminal())
+            .build()
+            .unwrap();
+
+        // Configs are always stored in meters
+        let checks = &config.states[0].checks;
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-3;
+        assert!(matches!(
+            checks[0].data,
+            CheckData::Altitude(FloatCondition::LessThan(meters)) if close(meters, 304.8)
+        ));
+        assert!(matches!(
+            checks[1].data,
+            CheckData::Altitude(FloatCondition::Between {
+                lower_bound,
+                upper_bound,
+            }) if close(lower_bound, 152.4) && close(upper_bound, 762.0)
+        ));
+    }
+
+    #[test]
+    fn test_state_names() {
+        let config = ConfigBuilder::new(0)
+            .state(StateBuilder::new(0).check(
+                CheckData::ApogeeFlag(NativeFlagCondition(true)),
+                Some(Transition::To(1)),
+            ))
+            .state(StateBuilder::new(1).name("Drogue").terminal())
+            .build()
+            .unwrap();
+
+        let label = |i| {
+            let mut label = heapless::String::<32>::new();
+            // # SAFETY: Both states were added
+            let index = unsafe { StateIndex::new_unchecked(i) };
+            write!(label, "{}", config.state_label(index)).unwrap();
+            label
+        };
+        assert_eq!(label(0), "0");
+        assert_eq!(label(1), "Drogue (1)");
+
+        // Names within the bound are kept as is, and longer ones are rejected
+        let mut name = heapless::String::<32>::new();
+        for _ in 0..crate::MAX_STATE_NAME_LEN {
+            name.push('a').unwrap();
+        }
+        let build = |name: &str| {
+            ConfigBuilder::new(0)
+                .state(StateBuilder::new(0).name(name).terminal())
+                .build()
+        };
+        assert_eq!(
+            build(&name).unwrap().states[0].name.as_deref(),
+            Some(name.as_str())
+        );
+        name.push('a').unwrap();
+        assert_eq!(build(&name), Err(BuildError::NameTooLong { state: 0 }));
+    }
+}
@@ -0,0 +1,17 @@
+//! The source of time that state and command timing is measured against. The flight computer
+//! implements [`Clock`] over its hardware timer, while tests use `mock::MockClock` so that time
+//! only moves when they advance it.
+
+use crate::Seconds;
+
+/// A monotonic clock
+pub trait Clock {
+    /// Returns the time since an arbitrary fixed point, such as boot
+    fn now(&self) -> Seconds;
+
+    /// Returns the seconds elapsed since `start`, an earlier time returned by [`Self::now`], such
+    /// as the time since a state was entered that its commands and timeout are polled with
+    fn elapsed_since(&self, start: Seconds) -> f32 {
+        self.now().0 - start.0
+    }
+}
@@ -0,0 +1,611 @@
+//! Reference configs for common flight profiles, built with the [`builder`](crate::builder).
+//!
+//! These are tested end to end with the simulator, so they double as regression tests for checks,
+//! timeouts, and commands working together, and as starting points for new configs.
+//!
+//! [`dual_deploy`] is also provided as [`DualDeployStates`], which can be declared in a `static`
+//! for flight builds without an allocator.
+
+use crate::builder::{ConfigBuilder, StateBuilder, Transition};
+use crate::detection::STANDARD_GRAVITY;
+use crate::frozen::FrozenVec;
+use crate::index::ConfigFile;
+use crate::reference::{Check, Command, State, StateTransition};
+use crate::{
+    AbortReason, CheckData, CommandObject, FloatCondition, NativeFlagCondition,
+    PyroContinuityCondition, Seconds,
+};
+
+/// How long each deployment charge is fired for
+const DEPLOY_PULSE: Seconds = Seconds(1.0);
+
+/// A dual-deploy recovery, which fires the drogue on pyro 1 at apogee and the main on pyro 2 once
+/// the rocket has descended below `main_altitude` meters.
+///
+/// The states, by index, are:
+///
+/// 0. `Ground`, which waits for liftoff, aborting to `Safe` if either igniter loses continuity
+/// 1. `Boost`, until the motor burns out
+/// 2. `Coast`, until apogee, which is locked out while the rocket is near the speed of sound
+/// 3. `Drogue`, which fires pyro 1 and waits to descend below `main_altitude`
+/// 4. `Main`, which fires pyro 2 and waits to land
+/// 5. `Landed`
+/// 6. `Safe`
+pub fn dual_deploy(main_altitude: f32) -> ConfigFile {
+    build_dual_deploy(main_altitude, None)
+}
+
+/// A [`dual_deploy`] with a backup timer, which fires the drogue `apogee_backup` after burnout if
+/// apogee has not been detected by then, such as if the barometer failed.
+///
+/// The backup is an abort from `Coast` to `Drogue` with [`AbortReason::Timeout`], so it stands out
+/// in the flight log. Once the drogue is out, the main deploys by altitude as usual.
+pub fn dual_deploy_with_backup(main_altitude: f32, apogee_backup: Seconds) -> ConfigFile {
+    build_dual_deploy(main_altitude, Some(apogee_backup))
+}
+
+fn build_dual_deploy(main_altitude: f32, apogee_backup: Option<Seconds>) -> ConfigFile {
+    let mut coast = StateBuilder::new(2).name("Coast").all_of(
+        &[
+            CheckData::ApogeeFlag(NativeFlagCondition(true)),
+            CheckData::MachLockFlag(NativeFlagCondition(false)),
+        ],
+        Transition::To(3),
+    );
+    if let Some(backup) = apogee_backup {
+        coast = coast.timeout(backup.0, Transition::Abort(3, AbortReason::Timeout));
+    }
+
+    ConfigBuilder::new(0)
+        .state(
+            StateBuilder::new(0)
+                .name("Ground")
+                .check(
+                    CheckData::Acceleration(FloatCondition::GreaterThan(2.0 * STANDARD_GRAVITY)),
+                    Some(Transition::To(1)),
+                )
+                .check(
+                    CheckData::Pyro1Continuity(PyroContinuityCondition(false)),
+                    Some(Transition::Abort(6, AbortReason::ContinuityLoss)),
+                )
+                .check(
+                    CheckData::Pyro2Continuity(PyroContinuityCondition(false)),
+                    Some(Transition::Abort(6, AbortReason::ContinuityLoss)),
+                ),
+        )
+        .state(StateBuilder::new(1).name("Boost").check(
+            CheckData::BurnoutFlag(NativeFlagCondition(true)),
+            Some(Transition::To(2)),
+        ))
+        .state(coast)
+        .state(
+            StateBuilder::new(3)
+                .name("Drogue")
+                .pulse(CommandObject::Pyro1(true), Seconds(0.0), DEPLOY_PULSE)
+                .all_of(
+                    &[
+                        CheckData::Altitude(FloatCondition::LessThan(main_altitude)),
+                        CheckData::Velocity(FloatCondition::LessThan(0.0)),
+                    ],
+                    Transition::To(4),
+                ),
+        )
+        .state(
+            StateBuilder::new(4)
+                .name("Main")
+                .pulse(CommandObject::Pyro2(true), Seconds(0.0), DEPLOY_PULSE)
+                .check(
+                    CheckData::LandedFlag(NativeFlagCondition(true)),
+                    Some(Transition::To(5)),
+                ),
+        )
+        .state(StateBuilder::new(5).name("Landed").terminal())
+        .state(StateBuilder::new(6).name("Safe").terminal())
+        .build()
+        // Every state and transition above is in bounds
+        .unwrap()
+}
+
+/// A typical main deployment altitude, in meters
+pub const DEFAULT_MAIN_ALTITUDE: f32 = 300.0;
+
+/// Storage for the [`dual_deploy`] config as reference states, so it can be run without an
+/// allocator or [`indices_to_refs`](crate::indices_to_refs).
+///
+/// [`Self::new`] is a `const fn`, so the firmware can declare the storage in a `static` that it
+/// owns, such as with `cortex_m::singleton!`, and [`Self::init`] links the states together in
+/// place. The states are unnamed, as a name can't be built in a `const`
+pub struct DualDeployStates<'s> {
+    states: [State<'s>; 7],
+    /// The checks of each state, in order
+    checks: [Check<'s>; 9],
+    commands: [Command; 2],
+}
+
+impl<'s> DualDeployStates<'s> {
+    /// Creates the states, checks, and commands of [`dual_deploy`] with the main at
+    /// `main_altitude`, not yet linked together
+    pub const fn new(main_altitude: f32) -> Self {
+        Self {
+            states: [
+                State::new_complete(0, FrozenVec::new(), FrozenVec::new(), None),
+                State::new_complete(1, FrozenVec::new(), FrozenVec::new(), None),
+                State::new_complete(2, FrozenVec::new(), FrozenVec::new(), None),
+                State::new_complete(3, FrozenVec::new(), FrozenVec::new(), None),
+                State::new_complete(4, FrozenVec::new(), FrozenVec::new(), None),
+                State::new_complete(5, FrozenVec::new(), FrozenVec::new(), None),
+                State::new_complete(6, FrozenVec::new(), FrozenVec::new(), None),
+            ],
+            checks: [
+                Check::new(
+                    CheckData::Acceleration(FloatCondition::GreaterThan(2.0 * STANDARD_GRAVITY)),
+                    None,
+                    0,
+                ),
+                Check::new(
+                    CheckData::Pyro1Continuity(PyroContinuityCondition(false)),
+                    None,
+                    1,
+                ),
+                Check::new(
+                    CheckData::Pyro2Continuity(PyroContinuityCondition(false)),
+                    None,
+                    2,
+                ),
+                Check::new(CheckData::BurnoutFlag(NativeFlagCondition(true)), None, 0),
+                grouped(Check::new(
+                    CheckData::ApogeeFlag(NativeFlagCondition(true)),
+                    None,
+                    0,
+                )),
+                grouped(Check::new(
+                    CheckData::MachLockFlag(NativeFlagCondition(false)),
+                    None,
+                    1,
+                )),
+                grouped(Check::new(
+                    CheckData::Altitude(FloatCondition::LessThan(main_altitude)),
+                    None,
+                    0,
+                )),
+                grouped(Check::new(
+                    CheckData::Velocity(FloatCondition::LessThan(0.0)),
+                    None,
+                    1,
+                )),
+                Check::new(CheckData::LandedFlag(NativeFlagCondition(true)), None, 0),
+            ],
+            commands: [
+                pulsed(CommandObject::Pyro1(true)),
+                pulsed(CommandObject::Pyro2(true)),
+            ],
+        }
+    }
+
+    /// Links the checks, commands, and transitions into the states, and returns the states with
+    /// the default state first. This borrows the storage for as long as the states are used
+    pub fn init(&'s mut self) -> &'s [State<'s>] {
+        let Self {
+            states,
+            checks,
+            commands,
+        } = self;
+        let states: &'s [State<'s>; 7] = states;
+
+        let continuity_lost = Some(StateTransition::Abort(
+            &states[6],
+            AbortReason::ContinuityLoss,
+        ));
+        let transitions = [
+            Some(StateTransition::Transition(&states[1])),
+            continuity_lost,
+            continuity_lost,
+            Some(StateTransition::Transition(&states[2])),
+            Some(StateTransition::Transition(&states[3])),
+            None,
+            Some(StateTransition::Transition(&states[4])),
+            None,
+            Some(StateTransition::Transition(&states[5])),
+        ];
+        for (check, transition) in checks.iter_mut().zip(transitions) {
+            check.transition = transition;
+        }
+        let checks: &'s [Check<'s>; 9] = checks;
+        let commands: &'s [Command; 2] = commands;
+
+        // The checks and commands of each state that has any
+        let layout: [(&[Check<'s>], &[Command]); 5] = [
+            (&checks[0..3], &[]),
+            (&checks[3..4], &[]),
+            (&checks[4..6], &[]),
+            (&checks[6..8], &commands[0..1]),
+            (&checks[8..9], &commands[1..2]),
+        ];
+        for (state, (checks, commands)) in states.iter().zip(layout) {
+            // No state has more checks or commands than fit
+            for check in checks {
+                let _ = state.checks.push(check);
+            }
+            for command in commands {
+                let _ = state.commands.push(command);
+            }
+        }
+
+        states
+    }
+}
+
+/// Puts `check` in the first group of its state, as [`StateBuilder::all_of`] does
+const fn grouped(check: Check<'_>) -> Check<'_> {
+    Check {
+        group: Some(0),
+        ..check
+    }
+}
+
+/// A command firing `object` on entering its state for [`DEPLOY_PULSE`], as
+/// [`StateBuilder::pulse`] does
+const fn pulsed(object: CommandObject) -> Command {
+    Command {
+        pulse: Some(DEPLOY_PULSE),
+        ..Command::new(object, Seconds(0.0))
+    }
+}
+
+/// The conditions under which a sustainer may be ignited, see [`airstart`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StagingLimits {
+    /// The slowest vertical velocity, in m/s, at which the sustainer may be ignited
+    pub min_velocity: f32,
+    /// The furthest from vertical, in degrees, at which the sustainer may be ignited
+    pub max_tilt: f32,
+    /// The earliest time after liftoff at which the sustainer may be ignited
+    pub earliest: Seconds,
+    /// The time after liftoff at which the sustainer is given up on
+    pub latest: Seconds,
+}
+
+/// A two-stage flight, which ignites the sustainer on pyro 3 once the booster has burnt out, but
+/// only while the rocket is fast enough, close enough to vertical, and within the ignition window
+/// of `limits`. If the conditions are not all met before the window closes, ignition is aborted
+/// and the rocket coasts to apogee unpowered. Either way, the drogue is fired on pyro 1 at apogee.
+///
+/// The states, by index, are:
+///
+/// 0. `Ground`, which waits for liftoff
+/// 1. `Boost`, which waits for burnout and the ignition conditions
+/// 2. `Sustainer`, which fires pyro 3 and waits for apogee
+/// 3. `Unstaged`, the abort when ignition was inhibited, which waits for apogee
+/// 4. `Recovery`, which fires pyro 1
+pub fn airstart(limits: StagingLimits) -> ConfigFile {
+    let apogee = CheckData::ApogeeFlag(NativeFlagCondition(true));
+    ConfigBuilder::new(0)
+        .state(StateBuilder::new(0).name("Ground").check(
+            CheckData::Acceleration(FloatCondition::GreaterThan(2.0 * STANDARD_GRAVITY)),
+            Some(Transition::To(1)),
+        ))
+        .state(
+            StateBuilder::new(1)
+                .name("Boost")
+                .all_of(
+                    &[
+                        CheckData::BurnoutFlag(NativeFlagCondition(true)),
+                        CheckData::Velocity(FloatCondition::GreaterThan(limits.min_velocity)),
+                        CheckData::Tilt(FloatCondition::LessThan(limits.max_tilt)),
+                    ],
+                    Transition::To(2),
+                )
+                // `Boost` is entered at liftoff, so its dwell and timeout bound the window
+                .min_dwell(limits.earliest)
+                .timeout(limits.latest.0, Transition::Abort(3, AbortReason::Timeout)),
+        )
+        .state(
+            StateBuilder::new(2)
+                .name("Sustainer")
+                .pulse(CommandObject::Pyro3(true), Seconds(0.0), DEPLOY_PULSE)
+                .check(apogee, Some(Transition::To(4))),
+        )
+        .state(
+            StateBuilder::new(3)
+                .name("Unstaged")
+                .check(apogee, Some(Transition::To(4))),
+        )
+        .state(
+            StateBuilder::new(4)
+                .name("Recovery")
+                .pulse(CommandObject::Pyro1(true), Seconds(0.0), DEPLOY_PULSE)
+                .terminal(),
+        )
+        .build()
+        // Every state and transition above is in bounds
+        .unwrap()
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{
+        airstart, dual_deploy, dual_deploy_with_backup, DualDeployStates, StagingLimits,
+        DEFAULT_MAIN_ALTITUDE,
+    };
+    use crate::reference::StateTransition;
+    use crate::sim::{Event, FlightProfile, Simulator};
+    use crate::{indices_to_refs, AbortReason, CheckKind, CommandObject, Seconds};
+
+    use static_alloc::Bump;
+
+    /// Gives each simulation its own allocator, since a bump allocator never frees and the tests
+    /// run in parallel
+    fn allocator() -> &'static Bump<[u8; 1 << 14]> {
+        Box::leak(Box::new(Bump::uninit()))
+    }
+
+    #[test]
+    fn test_dual_deploy() {
+        let config = dual_deploy(300.0);
+        assert!(crate::validate(&config).is_ok());
+
+        let profile = FlightProfile {
+            main_altitude: 300.0,
+            ..FlightProfile::default()
+        };
+        let samples = profile.samples();
+        let mut sim = Simulator::new(&config, allocator()).unwrap();
+        sim.run(samples.iter().copied());
+
+        let transitions: std::vec::Vec<_> = sim
+            .events()
+            .iter()
+            .filter_map(|event| match *event {
+                Event::Transition { from, to, .. } => Some((from, to)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(transitions, [(0, 1), (1, 2), (2, 3), (3, 4), (4, 5)]);
+        assert_eq!(sim.current_state(), 5);
+
+        // The drogue fires on the first sample after apogee
+        let apogee = samples.iter().find(|sample| sample.apogee).unwrap();
+        assert_eq!(
+            sim.first_command(CommandObject::Pyro1(true)),
+            Some(apogee.time)
+        );
+        let drogue_off = sim.first_command(CommandObject::Pyro1(false)).unwrap();
+        assert!((drogue_off - apogee.time - 1.0).abs() < 0.02);
+
+        // The main fires on the first sample below the main altitude
+        let main_time = sim.first_command(CommandObject::Pyro2(true)).unwrap();
+        let main = samples
+            .iter()
+            .find(|sample| sample.time == main_time)
+            .unwrap();
+        let previous = samples.iter().rev().find(|s| s.time < main_time).unwrap();
+        assert!(main.altitude < 300.0 && previous.altitude >= 300.0);
+        assert!(main.velocity < 0.0);
+    }
+
+    /// Returns the id of the state `transition` goes to, and why if it is an abort
+    fn target(transition: Option<StateTransition>) -> Option<(u8, Option<AbortReason>)> {
+        transition.map(|transition| match transition {
+            StateTransition::Transition(to) => (to.id, None),
+            StateTransition::Abort(to, reason) => (to.id, Some(reason)),
+        })
+    }
+
+    // This failing to compile means part of the reference representation is no longer
+    // const-constructible
+    #[test]
+    fn test_frozen_dual_deploy() {
+        let mut storage = const { DualDeployStates::new(DEFAULT_MAIN_ALTITUDE) };
+        let frozen = storage.init();
+        let built = indices_to_refs(&dual_deploy(DEFAULT_MAIN_ALTITUDE), allocator()).unwrap();
+
+        // The same graph as the built config, other than the names
+        assert_eq!(frozen.len(), built.len());
+        for (frozen, built) in frozen.iter().zip(built.iter()) {
+            assert_eq!(frozen.id, built.id);
+            assert_eq!(frozen.checks.len(), built.checks.len());
+            for (frozen, built) in frozen.checks.iter().zip(built.checks.iter()) {
+                assert_eq!(frozen.data, built.data);
+                assert_eq!(frozen.debounce, built.debounce);
+                assert_eq!(frozen.group, built.group);
+                assert_eq!(frozen.index, built.index);
+                assert_eq!(target(frozen.transition), target(built.transition));
+            }
+            assert_eq!(frozen.commands.len(), built.commands.len());
+            for (frozen, built) in frozen.commands.iter().zip(built.commands.iter()) {
+                assert_eq!(frozen.object, built.object);
+                assert_eq!(frozen.delay, built.delay);
+                assert_eq!(frozen.repeat, built.repeat);
+                assert_eq!(frozen.pulse, built.pulse);
+            }
+            assert_eq!(
+                target(frozen.timeout.get().map(|timeout| timeout.transition)),
+                target(built.timeout.get().map(|timeout| timeout.transition))
+            );
+            assert_eq!(target(frozen.fault_transition.get()), None);
+        }
+
+        // And it runs without ever having been converted
+        let mut current = &frozen[0];
+        let steps: [&[CheckKind]; 5] = [
+            &[CheckKind::Acceleration],
+            &[CheckKind::BurnoutFlag],
+            &[CheckKind::ApogeeFlag, CheckKind::MachLockFlag],
+            &[CheckKind::Altitude, CheckKind::Velocity],
+            &[CheckKind::LandedFlag],
+        ];
+        for satisfied in steps {
+            current.reset_checks();
+            match current.evaluate_checks(|data| satisfied.contains(&data.kind())) {
+                Some((_, StateTransition::Transition(to))) => current = to,
+                _ => panic!("expected a transition out of state {}", current.id),
+            }
+        }
+        assert_eq!(current.id, 5);
+    }
+
+    #[test]
+    fn test_dual_deploy_continuity_loss() {
+        let samples = FlightProfile::default()
+            .samples()
+            .into_iter()
+            .map(|mut sample| {
+                sample.continuity[1] = false;
+                sample
+            });
+        let mut sim = Simulator::new(&dual_deploy(300.0), allocator()).unwrap();
+        sim.run(samples);
+
+        assert_eq!(
+            sim.events()[0],
+            Event::Transition {
+                time: 0.0,
+                from: 0,
+                to: 6,
+                abort: Some(AbortReason::ContinuityLoss),
+            }
+        );
+        assert_eq!(sim.first_command(CommandObject::Pyro1(true)), None);
+        assert_eq!(sim.first_command(CommandObject::Pyro2(true)), None);
+    }
+
+    #[test]
+    fn test_apogee_backup() {
+        let config = dual_deploy_with_backup(300.0, Seconds(16.0));
+        assert!(crate::validate(&config).is_ok());
+
+        // The barometer failed, so apogee is never detected
+        let failed = FlightProfile {
+            detects_apogee: false,
+            ..FlightProfile::default()
+        };
+        let samples = failed.samples();
+        let mut sim = Simulator::new(&config, allocator()).unwrap();
+        sim.run(samples.iter().copied());
+
+        let burnout = failed.pad_time + failed.burn_time;
+        let drogue = sim.first_command(CommandObject::Pyro1(true)).unwrap();
+        assert!((drogue - burnout - 16.0).abs() < 0.02, "{}", drogue);
+        assert!(sim.events().iter().any(|event| matches!(
+            event,
+            Event::Transition {
+                from: 2,
+                to: 3,
+                abort: Some(AbortReason::Timeout),
+                ..
+            }
+        )));
+        assert!(sim.first_command(CommandObject::Pyro2(true)).unwrap() > drogue);
+        assert_eq!(sim.current_state(), 5);
+
+        // When apogee is detected the backup never fires, so the drogue is only fired once
+        let mut sim = Simulator::new(&config, allocator()).unwrap();
+        sim.run(FlightProfile::default().samples());
+        let apogee = FlightProfile::default()
+            .samples()
+            .into_iter()
+            .find(|sample| sample.apogee)
+            .unwrap();
+        assert_eq!(
+            sim.first_command(CommandObject::Pyro1(true)),
+            Some(apogee.time)
+        );
+        let drogue_fires = sim
+            .events()
+            .iter()
+            .filter(|event| {
+                matches!(
+                    event,
+                    Event::Command {
+                        object: CommandObject::Pyro1(true),
+                        ..
+                    }
+                )
+            })
+            .count();
+        assert_eq!(drogue_fires, 1);
+        assert!(!sim
+            .events()
+            .iter()
+            .any(|event| matches!(event, Event::Transition { abort: Some(_), .. })));
+    }
+
+    fn staging_limits() -> StagingLimits {
+        StagingLimits {
+            min_velocity: 100.0,
+            max_tilt: 20.0,
+            earliest: Seconds(2.5),
+            latest: Seconds(6.0),
+        }
+    }
+
+    /// Flies `profile` through an airstart config with `limits`
+    fn fly_airstart(limits: StagingLimits, profile: FlightProfile) -> Simulator {
+        let config = airstart(limits);
+        assert!(crate::validate(&config).is_ok());
+        let mut sim = Simulator::new(&config, allocator()).unwrap();
+        sim.run(profile.samples());
+        sim
+    }
+
+    #[test]
+    fn test_airstart() {
+        let profile = FlightProfile::default();
+        let sim = fly_airstart(staging_limits(), profile);
+
+        // The sustainer lights at burnout, which is within the window
+        let burnout = profile.pad_time + profile.burn_time;
+        let ignition = sim.first_command(CommandObject::Pyro3(true)).unwrap();
+        assert!((ignition - burnout).abs() < 0.02, "{}", ignition);
+        assert!(sim.first_command(CommandObject::Pyro1(true)).unwrap() > ignition);
+        assert_eq!(sim.current_state(), 4);
+
+        // Burning out before the window opens waits for it
+        let limits = StagingLimits {
+            earliest: Seconds(4.0),
+            ..staging_limits()
+        };
+        let sim = fly_airstart(limits, profile);
+        let ignition = sim.first_command(CommandObject::Pyro3(true)).unwrap();
+        assert!(
+            (ignition - profile.pad_time - limits.earliest.0).abs() < 0.02,
+            "{}",
+            ignition
+        );
+    }
+
+    #[test]
+    fn test_airstart_inhibited() {
+        let tilted = FlightProfile {
+            tilt: 25.0,
+            ..FlightProfile::default()
+        };
+        // The booster burns out at about 150 m/s
+        let slow = StagingLimits {
+            min_velocity: 200.0,
+            ..staging_limits()
+        };
+
+        for (limits, profile) in [(staging_limits(), tilted), (slow, FlightProfile::default())] {
+            let sim = fly_airstart(limits, profile);
+            assert_eq!(sim.first_command(CommandObject::Pyro3(true)), None);
+            let abort = sim.events().iter().find_map(|event| match *event {
+                Event::Transition {
+                    time,
+                    from: 1,
+                    to: 3,
+                    abort,
+                } => Some((time, abort)),
+                _ => None,
+            });
+            let (time, reason) = abort.unwrap();
+            let deadline = profile.pad_time + limits.latest.0;
+            assert!((time - deadline).abs() < 0.02, "{}", time);
+            assert_eq!(reason, Some(AbortReason::Timeout));
+
+            // The drogue still deploys
+            assert!(sim.first_command(CommandObject::Pyro1(true)).is_some());
+            assert_eq!(sim.current_state(), 4);
+        }
+    }
+}
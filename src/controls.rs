@@ -0,0 +1,26 @@
+//! The outputs that commands drive. The flight computer implements [`Controls`] over its pins,
+//! while tests use `mock::MockControls` to record what would have been set.
+
+use crate::CommandObject;
+
+/// Something that can carry out a command, such as the flight computer's pyro and beacon outputs
+pub trait Controls {
+    /// Sets the object a command acts upon to the state it was commanded to, such as driving
+    /// pyro channel 1 high for `CommandObject::Pyro1(true)`
+    fn set(&mut self, object: CommandObject);
+
+    /// Drives every pyro channel low, leaving the outputs in their safe state. This should be
+    /// called whenever the state machine aborts.
+    ///
+    /// It only calls [`Self::set`], so it can be called any number of times, and is safe to call
+    /// from a panic handler as long as `set` is
+    fn disarm_all(&mut self) {
+        for object in [
+            CommandObject::Pyro1(false),
+            CommandObject::Pyro2(false),
+            CommandObject::Pyro3(false),
+        ] {
+            self.set(object);
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::reference::Check;
-use crate::{index, reference};
+use crate::{index, reference, Feet, Meters, MAX_CHECKS_PER_STATE};
 
 use alloc::alloc;
 use alloc_traits::{Layout, LocalAlloc, NonZeroLayout};
@@ -8,18 +8,41 @@ use core::slice;
 
 type State = reference::State<'static>;
 
+/// An error encountered while converting an [`index::ConfigFile`] to reference states
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConversionError {
+    /// A state index pointed outside of the config's states
+    InvalidStateIndex { index: usize, len: usize },
+    /// The allocator did not have enough memory left for the reference states
+    OutOfMemory,
+}
+
 pub fn indices_to_refs(
     config: &index::ConfigFile,
     alloc: &'static dyn LocalAlloc<'static>,
-) -> Option<&'static [State]> {
+) -> Result<&'static [State], ConversionError> {
     let len = config.states.len();
+
+    // Check every index before allocating anything, so a corrupt config doesn't use up memory that
+    // can never be freed
+    check_index(config.default_state, len)?;
+    if let Some(guard) = &config.continuity_guard {
+        check_index(guard.abort_to, len)?;
+    }
+    for state in config.states.iter() {
+        for transition in state.transitions() {
+            check_index(transition.state(), len)?;
+        }
+    }
+
     let bytes = len * size_of::<State>();
     let align = align_of::<State>();
 
     // Unwrap always succeeds because align was obtained from `align_of`
     let layout: Layout = alloc::Layout::from_size_align(bytes, align).unwrap().into();
+    // Unwrap always succeeds because the default state index was in bounds, so `len` is not zero
     let layout = NonZeroLayout::from_layout(layout).unwrap();
-    let mem = alloc.alloc(layout)?;
+    let mem = alloc.alloc(layout).ok_or(ConversionError::OutOfMemory)?;
 
     // # SAFETY
     // 1. `mem` is a valid, aligned, non-null pointer
@@ -31,8 +54,11 @@ pub fn indices_to_refs(
         unsafe { slice::from_raw_parts_mut(mem.ptr.as_ptr() as *mut _, len) };
 
     // Create a new, initialized State at each position in the slice
-    for (i, state) in uninit.iter_mut().enumerate() {
-        *state = MaybeUninit::new(State::new(i as u8));
+    for (i, (state, idx_state)) in uninit.iter_mut().zip(config.states.iter()).enumerate() {
+        let mut ref_state = State::new(i as u8);
+        ref_state.min_dwell = idx_state.min_dwell;
+        ref_state.name = idx_state.name.clone();
+        *state = MaybeUninit::new(ref_state);
     }
 
     // # SAFETY: All of the slice's MaybeUninit<T> are initialized from the for loop above.
@@ -56,15 +82,25 @@ pub fn indices_to_refs(
     for (i, state) in config.states.iter().enumerate() {
         let ref_state = &init[i];
 
-        for check in state.checks.iter() {
+        // Order the checks once here, so evaluating them is still a single pass in order
+        let mut checks: heapless::Vec<(usize, &index::Check), MAX_CHECKS_PER_STATE> =
+            state.checks.iter().enumerate().collect();
+        checks.sort_by_key(|(_, check)| core::cmp::Reverse(check.priority));
+
+        for (index, check) in checks {
             let transition = check
                 .transition
                 .as_ref()
-                .map(|t| transition_index_to_ref(t, init));
+                .map(|t| transition_index_to_ref(t, init))
+                .transpose()?;
 
             // Create and add the check
-            let ref_check = Check::new(check.data, transition);
-            let ref_check = alloc_struct(ref_check, alloc).unwrap();
+            // The index fits, as there are at most `MAX_CHECKS_PER_STATE` checks
+            let mut ref_check = Check::new(check.data, transition, index as u8);
+            ref_check.debounce = check.debounce;
+            ref_check.group = check.group;
+            ref_check.priority = check.priority;
+            let ref_check = alloc_struct(ref_check, alloc).ok_or(ConversionError::OutOfMemory)?;
             if ref_state.checks.push(ref_check).is_err() {
                 // The size of `index::State::checks` and `reference::State::checks` is determined
                 // by the same constant, so it is impossible to for one vector to have more
@@ -74,7 +110,8 @@ pub fn indices_to_refs(
         }
 
         for command in state.commands.iter() {
-            let ref_command = alloc_struct(command_index_to_ref(command), alloc).unwrap();
+            let ref_command = alloc_struct(command_index_to_ref(command), alloc)
+                .ok_or(ConversionError::OutOfMemory)?;
             if ref_state.commands.push(ref_command).is_err() {
                 // The size of `index::State::commands` and `reference::State::commands` is determined
                 // by the same constant, so it is impossible to for one vector to have more
@@ -84,33 +121,63 @@ pub fn indices_to_refs(
         }
 
         if let Some(timeout) = &state.timeout {
-            let timeout_transition = transition_index_to_ref(&timeout.transition, init);
+            let timeout_transition = transition_index_to_ref(&timeout.transition, init)?;
             let ref_timeout = Some(reference::Timeout::new(timeout.time, timeout_transition));
             ref_state.timeout.set(ref_timeout);
         }
+
+        if let Some(fault_transition) = &state.fault_transition {
+            let ref_fault_transition = transition_index_to_ref(fault_transition, init)?;
+            ref_state.fault_transition.set(Some(ref_fault_transition));
+        }
+
+        if let Some(guard) = &config.continuity_guard {
+            let abort = reference::StateTransition::Abort(
+                &init[usize::from(guard.abort_to)],
+                crate::AbortReason::ContinuityLoss,
+            );
+            let ref_guard = reference::ContinuityGuard::new(guard.timeout.0, abort);
+            ref_state.continuity_guard.set(Some(ref_guard));
+        }
     }
 
-    Some(init)
+    Ok(init)
+}
+
+fn check_index(index: index::StateIndex, len: usize) -> Result<(), ConversionError> {
+    let index = usize::from(index);
+    if index < len {
+        Ok(())
+    } else {
+        Err(ConversionError::InvalidStateIndex { index, len })
+    }
 }
 
 fn command_index_to_ref(command: &index::Command) -> reference::Command {
-    reference::Command::new(command.object, command.delay)
+    let mut ref_command = reference::Command::new(command.object, command.delay);
+    ref_command.repeat = command.repeat;
+    ref_command.pulse = command.pulse;
+    ref_command
 }
 
 fn transition_index_to_ref<'s>(
     transition: &index::StateTransition,
     ref_states: &'s [reference::State<'s>],
-) -> reference::StateTransition<'s> {
-    match transition {
-        index::StateTransition::Transition(s) => {
-            let dest_state = ref_states.get::<usize>((*s).into()).unwrap();
-            reference::StateTransition::Transition(dest_state)
+) -> Result<reference::StateTransition<'s>, ConversionError> {
+    let index = usize::from(transition.state());
+    let dest_state = ref_states
+        .get(index)
+        .ok_or(ConversionError::InvalidStateIndex {
+            index,
+            len: ref_states.len(),
+        })?;
+
+    Ok(match transition {
+        index::StateTransition::Transition(_) => reference::StateTransition::Transition(dest_state),
+        index::StateTransition::Abort(_, reason) => {
+            reference::StateTransition::Abort(dest_state, *reason)
         }
-        index::StateTransition::Abort(s) => {
-            let dest_state = ref_states.get::<usize>((*s).into()).unwrap();
-            reference::StateTransition::Abort(dest_state)
-        }
-    }
+    })
 }
 
 fn alloc_struct<T>(obj: T, alloc: &'static dyn LocalAlloc<'static>) -> Option<&'static T> {
@@ -128,13 +195,55 @@ fn alloc_struct<T>(obj: T, alloc: &'static dyn LocalAlloc<'static>) -> Option<&'
     Some(unsafe { &*ptr })
 }
 
+/// The number of meters in a foot, which is exact by definition
+pub const METERS_PER_FOOT: f32 = 0.3048;
+
+impl From<Feet> for Meters {
+    fn from(feet: Feet) -> Self {
+        Meters(feet.0 * METERS_PER_FOOT)
+    }
+}
+
+impl From<Meters> for Feet {
+    fn from(meters: Meters) -> Self {
+        Feet(meters.0 / METERS_PER_FOOT)
+    }
+}
+
+/// Standard atmosphere pressure at sea level in pascals
+pub const SEA_LEVEL_PRESSURE_PA: f32 = 101_325.0;
+
+/// The range of pressures, in pascals, that [`pressure_to_altitude`] accepts. Pressures outside of
+/// it are saturated, as they cannot come from a working barometer in flight
+const PRESSURE_RANGE_PA: (f32, f32) = (100.0, 120_000.0);
+
+/// Converts a barometric `pressure` in pascals to an altitude in meters using the standard
+/// atmosphere formula.
+///
+/// The altitude is relative to `ground_pressure` if one is given (AGL), otherwise it is relative
+/// to [`SEA_LEVEL_PRESSURE_PA`] (MSL). A NaN pressure produces a NaN altitude.
+pub fn pressure_to_altitude(pressure: f32, ground_pressure: Option<f32>) -> f32 {
+    let (min, max) = PRESSURE_RANGE_PA;
+    let pressure = pressure.clamp(min, max);
+    let reference = ground_pressure
+        .unwrap_or(SEA_LEVEL_PRESSURE_PA)
+        .clamp(min, max);
+
+    44_330.0 * (1.0 - libm::powf(pressure / reference, 1.0 / 5.255))
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{pressure_to_altitude, ConversionError, SEA_LEVEL_PRESSURE_PA};
+    use crate::builder::{ConfigBuilder, StateBuilder, Transition};
     use crate::{
         index::{Check, Command, ConfigFile, State, StateIndex, StateTransition, Timeout},
-        indices_to_refs, CheckData, CommandObject, FloatCondition, NativeFlagCondition,
-        PyroContinuityCondition, Seconds, MAX_CHECKS_PER_STATE, MAX_COMMANDS_PER_STATE, MAX_STATES,
+        indices_to_refs, AbortReason, CheckData, CommandObject, FloatCondition,
+        NativeFlagCondition, PyroContinuityCondition, Repeat, Seconds, MAX_CHECKS_PER_STATE,
+        MAX_COMMANDS_PER_STATE, MAX_STATES,
     };
+    use crate::{Feet, Meters};
+    use core::fmt::Write;
     use heapless::Vec;
     use static_alloc::Bump;
 
@@ -147,6 +256,71 @@ mod tests {
 
     static A: Bump<[u8; BUMP_SIZE]> = Bump::uninit();
 
+    #[test]
+    fn test_invalid_state_index() {
+        let mut states = Vec::new();
+
+        // # SAFETY: This index is deliberately out of range
+        let missing_idx = unsafe { StateIndex::new_unchecked(5) };
+        let mut checks = Vec::new();
+        checks
+            .push(Check::new(
+                CheckData::ApogeeFlag(NativeFlagCondition(true)),
+                Some(StateTransition::Transition(missing_idx)),
+            ))
+            .unwrap();
+        states.push(State::new(checks, Vec::new(), None)).unwrap();
+        states
+            .push(State::new(Vec::new(), Vec::new(), None))
+            .unwrap();
+
+        let config = ConfigFile {
+            // # SAFETY: We just pushed this state
+            default_state: unsafe { StateIndex::new_unchecked(0) },
+            states,
+            continuity_guard: None,
+        };
+
+        assert_eq!(
+            indices_to_refs(&config, &A).err(),
+            Some(ConversionError::InvalidStateIndex { index: 5, len: 2 })
+        );
+    }
+
+    #[test]
+    fn test_feet_to_meters() {
+        assert!((Meters::from(Feet(1000.0)).0 - 304.8).abs() < 1e-4);
+        assert_eq!(Feet::from(Meters(0.0)), Feet(0.0));
+
+        for feet in [-50.0, 1.0, 700.0, 10_000.0] {
+            let round_trip = Feet::from(Meters::from(Feet(feet))).0;
+            assert!(
+                (round_trip - feet).abs() < feet.abs() * 1e-6,
+                "{}",
+                round_trip
+            );
+        }
+    }
+
+    #[test]
+    fn test_pressure_to_altitude() {
+        assert!(pressure_to_altitude(SEA_LEVEL_PRESSURE_PA, None).abs() < 0.01);
+
+        // 1000m in the standard atmosphere
+        assert!((pressure_to_altitude(89_874.6, None) - 1000.0).abs() < 1.0);
+
+        // Relative to a ground reference
+        assert!(pressure_to_altitude(89_874.6, Some(89_874.6)).abs() < 0.01);
+        assert!(pressure_to_altitude(97_000.0, Some(98_000.0)) > 0.0);
+
+        // Nonsensical pressures saturate instead of producing NaN
+        assert!(pressure_to_altitude(-50.0, None).is_finite());
+        assert_eq!(
+            pressure_to_altitude(1_000_000.0, None),
+            pressure_to_altitude(120_000.0, None)
+        );
+    }
+
     #[test]
     fn test_indices_to_refs() {
         let mut states = Vec::new();
@@ -170,9 +344,12 @@ mod tests {
         // time = 0.0
         //
         let mut descent_commands = Vec::new();
-        descent_commands
-            .push(Command::new(CommandObject::DataRate(20), Seconds(0.0)))
-            .unwrap();
+        let mut data_rate = Command::new(CommandObject::DataRate(20), Seconds(0.0));
+        data_rate.repeat = Repeat::OnReentry;
+        descent_commands.push(data_rate).unwrap();
+        let mut main_chute = Command::new(CommandObject::Pyro2(true), Seconds(0.0));
+        main_chute.pulse = Some(Seconds(0.5));
+        descent_commands.push(main_chute).unwrap();
         let descent = State::new(Vec::new(), descent_commands, None);
         states.push(descent).unwrap();
         // # SAFETY: We just pushed `descent`
@@ -190,13 +367,15 @@ mod tests {
         // transition = "Descent"
         //
         let mut flight_checks = Vec::new();
-        flight_checks
-            .push(Check::new(
-                CheckData::ApogeeFlag(NativeFlagCondition(true)),
-                Some(StateTransition::Transition(descent_idx)),
-            ))
-            .unwrap();
-        let flight = State::new(flight_checks, Vec::new(), None);
+        let mut apogee_check = Check::new(
+            CheckData::ApogeeFlag(NativeFlagCondition(true)),
+            Some(StateTransition::Transition(descent_idx)),
+        );
+        apogee_check.debounce = 3;
+        flight_checks.push(apogee_check).unwrap();
+        let mut flight = State::new(flight_checks, Vec::new(), None);
+        flight.min_dwell = Seconds(0.5);
+        flight.name = Some("Flight".into());
         states.push(flight).unwrap();
         // # SAFETY: We just pushed `flight`
         let flight_idx = unsafe { StateIndex::new_unchecked(states.len() as u8 - 1) };
@@ -253,26 +432,36 @@ mod tests {
         poweron_checks
             .push(Check::new(
                 CheckData::Pyro1Continuity(PyroContinuityCondition(false)),
-                Some(StateTransition::Abort(safe_idx)),
+                Some(StateTransition::Abort(
+                    safe_idx,
+                    AbortReason::ContinuityLoss,
+                )),
             ))
             .unwrap();
         poweron_checks
             .push(Check::new(
                 CheckData::Pyro2Continuity(PyroContinuityCondition(false)),
-                Some(StateTransition::Abort(safe_idx)),
+                Some(StateTransition::Abort(
+                    safe_idx,
+                    AbortReason::ContinuityLoss,
+                )),
             ))
             .unwrap();
         poweron_checks
             .push(Check::new(
                 CheckData::Pyro3Continuity(PyroContinuityCondition(false)),
-                Some(StateTransition::Abort(safe_idx)),
+                Some(StateTransition::Abort(
+                    safe_idx,
+                    AbortReason::ContinuityLoss,
+                )),
             ))
             .unwrap();
-        let poweron = State::new(
+        let mut poweron = State::new(
             poweron_checks,
             Vec::new(),
             Some(Timeout::new(1.0, StateTransition::Transition(launch_idx))),
         );
+        poweron.fault_transition = Some(StateTransition::Abort(safe_idx, AbortReason::SensorFault));
         states.push(poweron).unwrap();
         // # SAFETY: We just pushed `poweron`
         let poweron_idx = unsafe { StateIndex::new_unchecked(states.len() as u8 - 1) };
@@ -280,6 +469,7 @@ mod tests {
         let config = ConfigFile {
             default_state: poweron_idx,
             states: states.clone(),
+            continuity_guard: None,
         };
 
         let reference_cfg = indices_to_refs(&config, &A).unwrap();
@@ -289,9 +479,24 @@ mod tests {
             assert_eq!(state.id, i as u8);
             assert_eq!(state.checks.len(), idx_state.checks.len());
             assert_eq!(state.commands.len(), idx_state.commands.len());
+            assert_eq!(state.min_dwell, idx_state.min_dwell);
+            assert_eq!(state.name, idx_state.name);
+            if let Some(name) = &idx_state.name {
+                let (mut label, mut expected) =
+                    (heapless::String::<32>::new(), heapless::String::<32>::new());
+                write!(label, "{}", state.label()).unwrap();
+                write!(expected, "{} ({})", name, i).unwrap();
+                assert_eq!(label, expected);
+            }
+            assert_eq!(
+                state.fault_transition.get().map(transition_ref_to_index),
+                idx_state.fault_transition
+            );
 
             for (check, idx_check) in state.checks.iter().zip(idx_state.checks.iter()) {
                 assert_eq!(check.data, idx_check.data);
+                assert_eq!(check.debounce, idx_check.debounce);
+                assert_eq!(check.group, idx_check.group);
 
                 assert_eq!(check.transition.is_some(), idx_check.transition.is_some());
 
@@ -303,13 +508,15 @@ mod tests {
                             crate::index::StateTransition::Transition(idx) => {
                                 assert_eq!(s.id, usize::from(idx) as u8);
                             }
-                            crate::index::StateTransition::Abort(_) => {
+                            crate::index::StateTransition::Abort(_, _) => {
                                 panic!();
                             }
                         },
-                        crate::reference::StateTransition::Abort(s) => match idx_transition {
-                            crate::index::StateTransition::Abort(idx) => {
+                        crate::reference::StateTransition::Abort(s, reason) => match idx_transition
+                        {
+                            crate::index::StateTransition::Abort(idx, idx_reason) => {
                                 assert_eq!(s.id, usize::from(idx) as u8);
+                                assert_eq!(reason, idx_reason);
                             }
                             crate::index::StateTransition::Transition(_) => {
                                 panic!();
@@ -322,6 +529,56 @@ mod tests {
             for (command, idx_command) in state.commands.iter().zip(idx_state.commands.iter()) {
                 assert_eq!(command.object, idx_command.object);
                 assert_eq!(command.delay, idx_command.delay);
+                assert_eq!(command.repeat, idx_command.repeat);
+                assert_eq!(command.pulse, idx_command.pulse);
+            }
+        }
+    }
+
+    #[test]
+    fn test_check_priority() {
+        let config = ConfigBuilder::new(0)
+            .state(
+                StateBuilder::new(0)
+                    .check(
+                        CheckData::Altitude(FloatCondition::LessThan(300.0)),
+                        Some(Transition::To(1)),
+                    )
+                    .check_with_priority(
+                        CheckData::Tilt(FloatCondition::GreaterThan(30.0)),
+                        Some(Transition::Abort(2, AbortReason::TiltExceeded)),
+                        1,
+                    ),
+            )
+            .state(StateBuilder::new(1).terminal())
+            .state(StateBuilder::new(2).terminal())
+            .build()
+            .unwrap();
+
+        let states = indices_to_refs(&config, &A).unwrap();
+        let first = states[0].checks.iter().next().unwrap();
+        assert_eq!(first.priority, 1);
+        assert_eq!(first.index, 1);
+
+        // Both checks are satisfied, but the abort was listed second and wins on priority. It is
+        // still reported as the second check
+        match states[0].evaluate_checks(|_| true) {
+            Some((1, crate::reference::StateTransition::Abort(to, reason))) => {
+                assert_eq!(to.id, 2);
+                assert_eq!(reason, AbortReason::TiltExceeded);
+            }
+            _ => panic!("expected the tilt abort"),
+        }
+    }
+
+    fn transition_ref_to_index(transition: crate::reference::StateTransition) -> StateTransition {
+        // # SAFETY: The ids of reference states are their index in the original config
+        match transition {
+            crate::reference::StateTransition::Transition(s) => {
+                StateTransition::Transition(unsafe { StateIndex::new_unchecked(s.id) })
+            }
+            crate::reference::StateTransition::Abort(s, reason) => {
+                StateTransition::Abort(unsafe { StateIndex::new_unchecked(s.id) }, reason)
             }
         }
     }
@@ -0,0 +1,312 @@
+//! Decodes a flight log downloaded from the flash into a timeline that can be read or plotted on
+//! the ground.
+
+use crate::log::{records, LogError, LogRecord, RecordPayload};
+use crate::AbortReason;
+
+use std::fmt::Write;
+use std::string::String;
+use std::vec::Vec;
+
+/// The header row of [`DecodedLog::to_csv`]
+pub const CSV_HEADER: &str = "timestamp_ms,altitude_m,velocity_mps,acceleration_mps2,state";
+
+/// A flight log decoded from raw bytes
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedLog {
+    /// Every record that decoded, in the order it was logged
+    pub records: Vec<LogRecord>,
+    /// Set if the log ended in a record that did not decode, such as one cut short by a power loss,
+    /// to the offset of that record and the error
+    pub corrupt_tail: Option<(usize, LogError)>,
+}
+
+/// One state transition in a [`DecodedLog`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transition {
+    /// Milliseconds since boot
+    pub timestamp_ms: u32,
+    pub from: u8,
+    pub to: u8,
+    pub abort: Option<AbortReason>,
+}
+
+/// One sensor sample in a [`DecodedLog`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sample {
+    /// Milliseconds since boot
+    pub timestamp_ms: u32,
+    pub altitude: f32,
+    pub velocity: f32,
+    pub acceleration: f32,
+    pub tilt: f32,
+}
+
+impl DecodedLog {
+    /// Decodes the records in `buf`, stopping at erased flash or at the first record that does not
+    /// decode. Everything before a bad record is kept
+    pub fn decode(buf: &[u8]) -> Self {
+        let mut iter = records(buf);
+        let mut records = Vec::new();
+        let mut corrupt_tail = None;
+        loop {
+            let offset = iter.offset();
+            match iter.next() {
+                Some(Ok(record)) => records.push(record),
+                Some(Err(err)) => corrupt_tail = Some((offset, err)),
+                None => break,
+            }
+        }
+
+        Self {
+            records,
+            corrupt_tail,
+        }
+    }
+
+    /// Returns every state transition, in order
+    pub fn transitions(&self) -> Vec<Transition> {
+        self.records
+            .iter()
+            .filter_map(|record| match record.payload {
+                RecordPayload::StateTransition { from, to, abort } => Some(Transition {
+                    timestamp_ms: record.timestamp_ms,
+                    from,
+                    to,
+                    abort,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns every sensor sample, in order
+    pub fn samples(&self) -> Vec<Sample> {
+        self.records
+            .iter()
+            .filter_map(|record| match record.payload {
+                RecordPayload::SensorSample {
+                    altitude,
+                    velocity,
+                    acceleration,
+                    tilt,
+                } => Some(Sample {
+                    timestamp_ms: record.timestamp_ms,
+                    altitude,
+                    velocity,
+                    acceleration,
+                    tilt,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Renders the sensor samples and state transitions as CSV, with the columns in
+    /// [`CSV_HEADER`].
+    ///
+    /// Each sample and each transition is a row. Transitions leave the sensor columns blank, and
+    /// every row has the state the rocket was in after it. Rows before the first transition use
+    /// the state it left, and the state is blank if the log has no transitions at all.
+    pub fn to_csv(&self) -> String {
+        let mut state = self.transitions().first().map(|transition| transition.from);
+        let mut out = String::new();
+        out.push_str(CSV_HEADER);
+        out.push('\n');
+        // Writing to a `String` cannot fail
+        for record in &self.records {
+            match record.payload {
+                RecordPayload::SensorSample {
+                    altitude,
+                    velocity,
+                    acceleration,
+                    ..
+                } => {
+                    let _ = write!(
+                        out,
+                        "{},{},{},{},",
+                        record.timestamp_ms, altitude, velocity, acceleration
+                    );
+                }
+                RecordPayload::StateTransition { to, .. } => {
+                    state = Some(to);
+                    let _ = write!(out, "{},,,,", record.timestamp_ms);
+                }
+                _ => continue,
+            }
+            if let Some(state) = state {
+                let _ = write!(out, "{}", state);
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Renders the log with one record per line, followed by a note if the log ended in a corrupt
+    /// record
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        // Writing to a `String` cannot fail
+        for record in &self.records {
+            let _ = writeln!(out, "{}", record);
+        }
+        if let Some((offset, err)) = self.corrupt_tail {
+            let _ = writeln!(out, "corrupt record at byte {}: {:?}", offset, err);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecodedLog, Sample, Transition, CSV_HEADER};
+    use crate::log::{LogError, LogRecord, RecordPayload};
+    use crate::{AbortReason, CommandObject};
+
+    fn flight() -> [LogRecord; 5] {
+        let sample = |timestamp_ms, altitude| {
+            LogRecord::new(
+                timestamp_ms,
+                RecordPayload::SensorSample {
+                    altitude,
+                    velocity: 10.0,
+                    acceleration: -9.8,
+                    tilt: 2.0,
+                },
+            )
+        };
+        [
+            sample(0, 0.0),
+            LogRecord::new(
+                100,
+                RecordPayload::StateTransition {
+                    from: 0,
+                    to: 1,
+                    abort: None,
+                },
+            ),
+            sample(200, 15.5),
+            LogRecord::new(
+                250,
+                RecordPayload::StateTransition {
+                    from: 1,
+                    to: 3,
+                    abort: Some(AbortReason::TiltExceeded),
+                },
+            ),
+            LogRecord::new(
+                251,
+                RecordPayload::CommandExecuted(CommandObject::Pyro1(true)),
+            ),
+        ]
+    }
+
+    /// Encodes `records` back to back, followed by a page of erased flash
+    fn encode(records: &[LogRecord]) -> std::vec::Vec<u8> {
+        let mut buf = std::vec::Vec::new();
+        for record in records {
+            let mut encoded = [0; crate::log::MAX_RECORD_SIZE];
+            let len = record.encode(&mut encoded).unwrap();
+            buf.extend_from_slice(&encoded[..len]);
+        }
+        buf.extend_from_slice(&[crate::log::ERASED; crate::storage::PAGE_SIZE]);
+        buf
+    }
+
+    #[test]
+    fn test_decode() {
+        let records = flight();
+        let log = DecodedLog::decode(&encode(&records));
+        assert_eq!(log.records, records);
+        assert_eq!(log.corrupt_tail, None);
+
+        assert_eq!(
+            log.transitions(),
+            [
+                Transition {
+                    timestamp_ms: 100,
+                    from: 0,
+                    to: 1,
+                    abort: None,
+                },
+                Transition {
+                    timestamp_ms: 250,
+                    from: 1,
+                    to: 3,
+                    abort: Some(AbortReason::TiltExceeded),
+                },
+            ]
+        );
+        assert_eq!(log.samples().len(), 2);
+        assert_eq!(
+            log.samples()[1],
+            Sample {
+                timestamp_ms: 200,
+                altitude: 15.5,
+                velocity: 10.0,
+                acceleration: -9.8,
+                tilt: 2.0,
+            }
+        );
+
+        assert_eq!(
+            log.to_text(),
+            "0.000 sample alt=0.0 vel=10.0 acc=-9.8 tilt=2.0\n\
+             0.100 state 0 -> 1\n\
+             0.200 sample alt=15.5 vel=10.0 acc=-9.8 tilt=2.0\n\
+             0.250 state 1 -> 3 abort TiltExceeded\n\
+             0.251 command Pyro1 ON\n"
+        );
+    }
+
+    #[test]
+    fn test_corrupt_tail() {
+        let records = flight();
+        let buf = encode(&records);
+        let end = buf.len() - crate::storage::PAGE_SIZE;
+
+        // The power was lost partway through writing the last record
+        let log = DecodedLog::decode(&buf[..end - 2]);
+        assert_eq!(log.records, records[..4]);
+        let last_offset = end - records[4].encoded_size();
+        assert_eq!(
+            log.corrupt_tail,
+            Some((last_offset, LogError::BufferTooSmall))
+        );
+        assert!(log.to_text().ends_with(&format!(
+            "corrupt record at byte {}: BufferTooSmall\n",
+            last_offset
+        )));
+
+        // A garbage kind byte
+        let mut buf = buf;
+        buf[last_offset] = 0x42;
+        let log = DecodedLog::decode(&buf);
+        assert_eq!(log.records, records[..4]);
+        assert_eq!(
+            log.corrupt_tail,
+            Some((last_offset, LogError::UnknownKind(0x42)))
+        );
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let log = DecodedLog::decode(&encode(&flight()));
+        let csv = log.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some(CSV_HEADER));
+        assert_eq!(
+            lines.collect::<std::vec::Vec<_>>(),
+            [
+                "0,0,10,-9.8,0",
+                "100,,,,1",
+                "200,15.5,10,-9.8,1",
+                "250,,,,3",
+            ]
+        );
+
+        // Without any transitions the state is unknown
+        let log = DecodedLog::decode(&encode(&flight()[..1]));
+        assert_eq!(log.to_csv(), format!("{}\n0,0,10,-9.8,\n", CSV_HEADER));
+    }
+}
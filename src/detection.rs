@@ -0,0 +1,609 @@
+//! Detectors which turn raw sensor readings into the native flags that checks read, such as
+//! [`crate::CheckKind::LandedFlag`]. These only do the math, so data acquisition feeds them each
+//! new sample along with the time since the last one.
+
+use crate::workspace::Reading;
+use crate::{AbortReason, FloatCondition};
+
+/// Standard gravity in m/s²
+pub const STANDARD_GRAVITY: f32 = 9.806_65;
+
+/// Latches once the rocket has stayed near the ground and stopped moving for a sustained window,
+/// so a momentary stationary reading during descent does not count as landing
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LandingDetector {
+    /// How long, in seconds, the rocket must stay stationary before it is considered landed
+    pub window: f32,
+    /// The altitude in meters above the ground, below which the rocket is considered on the ground
+    pub altitude_tolerance: f32,
+    /// The speed in m/s, below which the rocket is considered stationary
+    pub velocity_tolerance: f32,
+    stationary_for: f32,
+    landed: bool,
+}
+
+impl LandingDetector {
+    /// Creates a detector that latches after `window` seconds stationary, with default tolerances
+    pub fn new(window: f32) -> Self {
+        Self {
+            window,
+            altitude_tolerance: 10.0,
+            velocity_tolerance: 1.0,
+            stationary_for: 0.0,
+            landed: false,
+        }
+    }
+
+    /// Updates the detector with a new sample taken `dt` seconds after the last one, returning
+    /// true once landed
+    pub fn update(&mut self, altitude: f32, velocity: f32, dt: f32) -> bool {
+        if self.landed {
+            return true;
+        }
+
+        // Comparisons against NaN are false, so an invalid reading is never stationary
+        let stationary = altitude < self.altitude_tolerance
+            && altitude > -self.altitude_tolerance
+            && velocity < self.velocity_tolerance
+            && velocity > -self.velocity_tolerance;
+        if stationary {
+            self.stationary_for += dt;
+        } else {
+            self.stationary_for = 0.0;
+        }
+
+        self.landed = self.stationary_for >= self.window;
+        self.landed
+    }
+
+    /// Returns true once the detector has latched
+    pub fn is_landed(&self) -> bool {
+        self.landed
+    }
+}
+
+/// Latches when the motor burns out, which is when acceleration falls below a threshold after a
+/// sustained boost. Requiring the boost first keeps noise on the pad from latching it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BurnoutDetector {
+    /// The acceleration in m/s², above which the motor is considered to be burning
+    pub boost_threshold: f32,
+    /// How long, in seconds, acceleration must stay above `boost_threshold` to count as a boost
+    pub min_boost_time: f32,
+    /// The acceleration in m/s², below which the motor is considered burnt out
+    pub burnout_threshold: f32,
+    boosting_for: f32,
+    boosted: bool,
+    burnt_out: bool,
+}
+
+impl BurnoutDetector {
+    /// Creates a detector with default thresholds of 2 g for boost and 0 g for burnout
+    pub fn new() -> Self {
+        Self {
+            boost_threshold: 2.0 * STANDARD_GRAVITY,
+            min_boost_time: 0.1,
+            burnout_threshold: 0.0,
+            boosting_for: 0.0,
+            boosted: false,
+            burnt_out: false,
+        }
+    }
+
+    /// Updates the detector with a new vertical acceleration sample taken `dt` seconds after the
+    /// last one, returning true once burnt out
+    pub fn update(&mut self, acceleration: f32, dt: f32) -> bool {
+        if self.burnt_out {
+            return true;
+        }
+
+        if !self.boosted {
+            if acceleration > self.boost_threshold {
+                self.boosting_for += dt;
+                self.boosted = self.boosting_for >= self.min_boost_time;
+            } else {
+                self.boosting_for = 0.0;
+            }
+        } else if acceleration < self.burnout_threshold {
+            self.burnt_out = true;
+        }
+
+        self.burnt_out
+    }
+
+    /// Returns true once the detector has latched
+    pub fn is_burnt_out(&self) -> bool {
+        self.burnt_out
+    }
+}
+
+impl Default for BurnoutDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Latches at apogee, once vertical velocity has turned from ascending to descending and stayed
+/// descending for a short window, so noise near the peak does not latch it early. It never
+/// un-latches during a flight
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ApogeeDetector {
+    /// The velocity in m/s, positive up, that must be exceeded before apogee can be detected.
+    /// This keeps noise on the pad from latching it
+    pub min_ascent_velocity: f32,
+    /// How long, in seconds, velocity must stay negative before apogee is detected
+    pub window: f32,
+    ascended: bool,
+    descending_for: f32,
+    apogee: bool,
+}
+
+impl ApogeeDetector {
+    /// Creates a detector that latches after `window` seconds of descent, once the rocket has
+    /// risen faster than 10 m/s
+    pub fn new(window: f32) -> Self {
+        Self {
+            min_ascent_velocity: 10.0,
+            window,
+            ascended: false,
+            descending_for: 0.0,
+            apogee: false,
+        }
+    }
+
+    /// Updates the detector with a new vertical velocity sample in m/s, positive up, taken `dt`
+    /// seconds after the last one, returning true once apogee has been detected
+    pub fn update(&mut self, velocity: f32, dt: f32) -> bool {
+        if self.apogee {
+            return true;
+        }
+
+        if velocity > self.min_ascent_velocity {
+            self.ascended = true;
+        }
+
+        // Comparisons against NaN are false, so an invalid reading restarts the window
+        if self.ascended && velocity < 0.0 {
+            self.descending_for += dt;
+        } else {
+            self.descending_for = 0.0;
+        }
+
+        self.apogee = self.ascended && self.descending_for >= self.window;
+        self.apogee
+    }
+
+    /// Returns true once the detector has latched
+    pub fn is_apogee(&self) -> bool {
+        self.apogee
+    }
+}
+
+/// An approximation of the speed of sound in m/s, at sea level on a standard day
+pub const SPEED_OF_SOUND: f32 = 343.0;
+
+/// Sets the Mach lockout flag while the rocket is moving near or above the speed of sound, so
+/// states that deploy can require it to be clear
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MachLockout {
+    /// The Mach number, at or above which deployment is locked out. This is below 1 to leave a
+    /// margin through the transonic region, where pressure readings are unreliable
+    pub threshold: f32,
+    locked: bool,
+}
+
+impl MachLockout {
+    /// Creates a lockout which engages at Mach 0.8
+    pub fn new() -> Self {
+        Self {
+            threshold: 0.8,
+            locked: false,
+        }
+    }
+
+    /// Updates the lockout with a new velocity sample in m/s, returning true while locked out.
+    /// An invalid velocity locks out, as the rocket could be going any speed
+    pub fn update(&mut self, velocity: f32) -> bool {
+        let mach = velocity / SPEED_OF_SOUND;
+        self.locked = !(mach < self.threshold && mach > -self.threshold);
+        self.locked
+    }
+
+    /// Returns true if the last sample was near or above the speed of sound
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+}
+
+impl Default for MachLockout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the speed of descent in m/s from a vertical velocity, positive up, for
+/// [`crate::CheckData::DescentRate`]. This is zero while ascending, and stays NaN for an invalid
+/// velocity so the fault is not hidden
+pub fn descent_rate(velocity: f32) -> f32 {
+    if velocity > 0.0 {
+        0.0
+    } else {
+        -velocity
+    }
+}
+
+/// Remembers when liftoff was first detected, so [`crate::CheckData::TimeSinceLiftoff`] can count
+/// from it
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct LiftoffClock {
+    liftoff_time: Option<f32>,
+}
+
+impl LiftoffClock {
+    pub fn new() -> Self {
+        Self { liftoff_time: None }
+    }
+
+    /// Updates the clock with the current liftoff flag, at `now` seconds since boot. The time is
+    /// only captured the first time the flag is set
+    pub fn update(&mut self, liftoff: bool, now: f32) {
+        if liftoff && self.liftoff_time.is_none() {
+            self.liftoff_time = Some(now);
+        }
+    }
+
+    /// Returns the seconds since liftoff at `now` seconds since boot, or `None` before liftoff
+    pub fn since_liftoff(&self, now: f32) -> Option<f32> {
+        self.liftoff_time.map(|liftoff_time| now - liftoff_time)
+    }
+
+    /// Returns true if `condition` is satisfied by the time since liftoff. This is never satisfied
+    /// before liftoff
+    pub fn is_satisfied(&self, condition: &FloatCondition, now: f32) -> bool {
+        self.since_liftoff(now)
+            .is_some_and(|elapsed| condition.is_satisfied(elapsed))
+    }
+}
+
+/// Why a sensor's readings can't be trusted
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SensorFault {
+    /// The reading was NaN or infinite
+    Invalid,
+    /// The reading is outside of what is physically possible for the sensor
+    OutOfRange,
+    /// The sensor has returned exactly the same reading for too many samples in a row
+    Stuck,
+}
+
+/// Watches the readings of a single sensor for faults, so the state machine can abort rather than
+/// trust bad data
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SensorMonitor {
+    /// The lowest physically possible reading
+    pub min: f32,
+    /// The highest physically possible reading
+    pub max: f32,
+    /// How many times in a row a reading may repeat exactly before the sensor is considered stuck
+    pub max_repeats: u16,
+    last: Option<f32>,
+    repeats: u16,
+    fault: Option<SensorFault>,
+}
+
+impl SensorMonitor {
+    pub fn new(min: f32, max: f32, max_repeats: u16) -> Self {
+        Self {
+            min,
+            max,
+            max_repeats,
+            last: None,
+            repeats: 0,
+            fault: None,
+        }
+    }
+
+    /// Checks a new reading, returning the fault it shows if any
+    pub fn update(&mut self, reading: f32) -> Option<SensorFault> {
+        if self.last == Some(reading) {
+            self.repeats = self.repeats.saturating_add(1);
+        } else {
+            self.repeats = 0;
+        }
+        self.last = Some(reading);
+
+        self.fault = if !reading.is_finite() {
+            Some(SensorFault::Invalid)
+        } else if reading < self.min || reading > self.max {
+            Some(SensorFault::OutOfRange)
+        } else if self.repeats > self.max_repeats {
+            Some(SensorFault::Stuck)
+        } else {
+            None
+        };
+        self.fault
+    }
+
+    /// Returns the fault shown by the last reading
+    pub fn fault(&self) -> Option<SensorFault> {
+        self.fault
+    }
+
+    /// Returns the reason to abort with if the last reading was faulty
+    pub fn abort_reason(&self) -> Option<AbortReason> {
+        self.fault.map(|_| AbortReason::SensorFault)
+    }
+}
+
+/// Decides whether each pyro channel, from 1 to 3, has an igniter connected from the raw reading
+/// of its continuity sense ADC. While a channel is firing its reading is meaningless, so the last
+/// stable value is kept until it stops
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ContinuitySensor {
+    /// The raw ADC reading at or above which a channel has continuity
+    pub threshold: u16,
+    continuity: [bool; 3],
+}
+
+impl ContinuitySensor {
+    /// Creates a sensor with no continuity on any channel until it is first updated
+    pub fn new(threshold: u16) -> Self {
+        Self {
+            threshold,
+            continuity: [false; 3],
+        }
+    }
+
+    /// Updates every channel with its raw `readings`, skipping any channel which is `firing`, and
+    /// returns whether each has continuity
+    pub fn update(&mut self, readings: [u16; 3], firing: [bool; 3]) -> [bool; 3] {
+        for ((continuity, reading), firing) in self.continuity.iter_mut().zip(readings).zip(firing)
+        {
+            if !firing {
+                *continuity = reading >= self.threshold;
+            }
+        }
+        self.continuity
+    }
+
+    /// Returns whether each channel had continuity at its last stable reading
+    pub fn continuity(&self) -> [bool; 3] {
+        self.continuity
+    }
+
+    /// Returns the continuity of each channel as readings for a
+    /// [`DataWorkspace`](crate::workspace::DataWorkspace)
+    pub fn readings(&self) -> [Reading; 3] {
+        let [pyro1, pyro2, pyro3] = self.continuity;
+        [
+            Reading::Pyro1Continuity(pyro1),
+            Reading::Pyro2Continuity(pyro2),
+            Reading::Pyro3Continuity(pyro3),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        descent_rate, ApogeeDetector, BurnoutDetector, ContinuitySensor, LandingDetector,
+        LiftoffClock, MachLockout, SensorFault, SensorMonitor, STANDARD_GRAVITY,
+    };
+    use crate::frozen::FrozenVec;
+    use crate::reference::{Check, State, StateTransition};
+    use crate::workspace::{DataWorkspace, Workspace};
+    use crate::{
+        AbortReason, CheckData, CheckKind, FloatCondition, NativeFlagCondition, ObjectState,
+    };
+
+    #[test]
+    fn test_landing_detector() {
+        let mut detector = LandingDetector::new(2.0);
+
+        // Descending under the main chute
+        assert!(!detector.update(150.0, -6.0, 0.5));
+
+        // A brief stationary blip, such as bouncing off a tree, should not latch
+        assert!(!detector.update(3.0, 0.2, 0.5));
+        assert!(!detector.update(3.0, 0.1, 0.5));
+        assert!(!detector.update(2.0, -4.0, 0.5));
+
+        // Sitting on the ground
+        for _ in 0..3 {
+            assert!(!detector.update(0.5, 0.0, 0.5));
+        }
+        assert!(detector.update(0.5, 0.0, 0.5));
+
+        // Once latched, it stays landed
+        assert!(detector.update(f32::NAN, 20.0, 0.5));
+        assert!(detector.is_landed());
+    }
+
+    #[test]
+    fn test_burnout_detector() {
+        let mut detector = BurnoutDetector::new();
+        let dt = 0.05;
+
+        // Noise on the pad, including a knock above the boost threshold, should never latch
+        for acceleration in [0.2, -0.5, 25.0, -3.0, 0.1] {
+            assert!(!detector.update(acceleration, dt));
+        }
+
+        // Boosting, still accelerating as the motor tails off
+        for acceleration in [45.0, 80.0, 75.0, 40.0, 5.0] {
+            assert!(!detector.update(acceleration, dt));
+        }
+
+        // Coasting
+        assert!(detector.update(-9.5, dt));
+        assert!(detector.update(30.0, dt));
+        assert!(detector.is_burnt_out());
+    }
+
+    #[test]
+    fn test_apogee_detector() {
+        let mut detector = ApogeeDetector::new(0.1);
+        let dt = 0.01;
+
+        // A knock on the pad is not an ascent, so the sign flip after it must not latch
+        for velocity in [
+            0.5, 4.0, -3.0, -3.0, -3.0, -3.0, -3.0, -3.0, -3.0, -3.0, -3.0, -3.0,
+        ] {
+            assert!(!detector.update(velocity, dt));
+        }
+
+        // Coasting up from 60 m/s with a little noise, then falling back down
+        let mut latched_at = None;
+        for tick in 0..1200 {
+            let time = tick as f32 * dt;
+            let noise = if tick % 2 == 0 { 0.3 } else { -0.3 };
+            let velocity = 60.0 - STANDARD_GRAVITY * time + noise;
+            if detector.update(velocity, dt) && latched_at.is_none() {
+                latched_at = Some(time);
+            }
+        }
+
+        // The peak is when velocity crosses zero, and the latch follows it by the window
+        let peak = 60.0 / STANDARD_GRAVITY;
+        let latched_at = latched_at.unwrap();
+        assert!(
+            latched_at > peak && latched_at < peak + 0.2,
+            "{}",
+            latched_at
+        );
+
+        // Once latched, it never un-latches
+        assert!(detector.update(50.0, dt));
+        assert!(detector.update(f32::NAN, dt));
+        assert!(detector.is_apogee());
+    }
+
+    #[test]
+    fn test_mach_lockout() {
+        let main = State::new(1);
+
+        // Deploy at apogee, but only while the Mach lockout is clear
+        let mut apogee = Check::new(
+            CheckData::ApogeeFlag(NativeFlagCondition(true)),
+            Some(StateTransition::Transition(&main)),
+            0,
+        );
+        apogee.group = Some(0);
+        let mut subsonic = Check::new(CheckData::MachLockFlag(NativeFlagCondition(false)), None, 1);
+        subsonic.group = Some(0);
+
+        let checks = FrozenVec::new();
+        assert!(checks.push(&apogee).is_ok());
+        assert!(checks.push(&subsonic).is_ok());
+        let drogue = State::new_complete(0, checks, FrozenVec::new(), None);
+
+        let mut lockout = MachLockout::new();
+        let mut deploys = |velocity| {
+            let locked = lockout.update(velocity);
+            drogue
+                .evaluate_checks(|data| match data {
+                    CheckData::ApogeeFlag(NativeFlagCondition(flag)) => *flag,
+                    CheckData::MachLockFlag(NativeFlagCondition(flag)) => *flag == locked,
+                    _ => false,
+                })
+                .is_some()
+        };
+
+        assert!(!deploys(320.0));
+        assert!(!deploys(-300.0));
+        assert!(!deploys(f32::NAN));
+        assert!(deploys(15.0));
+    }
+
+    #[test]
+    fn test_descent_rate() {
+        let condition = FloatCondition::GreaterThan(15.0);
+
+        assert_eq!(descent_rate(120.0), 0.0);
+        assert_eq!(descent_rate(-20.0), 20.0);
+        assert!(descent_rate(f32::NAN).is_nan());
+
+        assert!(!condition.is_satisfied(descent_rate(120.0)));
+        assert!(!condition.is_satisfied(descent_rate(-10.0)));
+        assert!(condition.is_satisfied(descent_rate(-20.0)));
+    }
+
+    #[test]
+    fn test_liftoff_clock() {
+        let mut clock = LiftoffClock::new();
+        let backup = FloatCondition::GreaterThan(8.0);
+
+        // Sitting on the pad long enough that a boot timer would have fired
+        clock.update(false, 60.0);
+        assert_eq!(clock.since_liftoff(60.0), None);
+        assert!(!clock.is_satisfied(&backup, 60.0));
+
+        clock.update(true, 100.0);
+        assert!(!clock.is_satisfied(&backup, 105.0));
+
+        // Later updates do not move the liftoff time
+        clock.update(true, 107.0);
+        assert_eq!(clock.since_liftoff(108.5), Some(8.5));
+        assert!(clock.is_satisfied(&backup, 108.5));
+    }
+
+    #[test]
+    fn test_stuck_sensor() {
+        let mut monitor = SensorMonitor::new(-500.0, 10_000.0, 3);
+
+        assert_eq!(monitor.update(100.0), None);
+        assert_eq!(monitor.update(100.5), None);
+        // The first reading and three repeats of it are allowed
+        for _ in 0..4 {
+            assert_eq!(monitor.update(100.25), None);
+        }
+        assert_eq!(monitor.update(100.25), Some(SensorFault::Stuck));
+        assert_eq!(monitor.abort_reason(), Some(AbortReason::SensorFault));
+
+        // The fault clears once the reading changes again
+        assert_eq!(monitor.update(101.0), None);
+        assert_eq!(monitor.abort_reason(), None);
+    }
+
+    #[test]
+    fn test_out_of_range_sensor() {
+        let mut monitor = SensorMonitor::new(-500.0, 10_000.0, 3);
+
+        assert_eq!(monitor.update(1200.0), None);
+        assert_eq!(monitor.update(45_000.0), Some(SensorFault::OutOfRange));
+        assert_eq!(monitor.update(1201.0), None);
+        assert_eq!(monitor.update(f32::NAN), Some(SensorFault::Invalid));
+        assert_eq!(monitor.fault(), Some(SensorFault::Invalid));
+    }
+
+    #[test]
+    fn test_continuity_sensor() {
+        let mut sensor = ContinuitySensor::new(2000);
+        // A mock ADC, with an igniter on pyro 1 and 3 but not 2
+        let mut adc = [3100, 150, 2000];
+
+        assert_eq!(sensor.update(adc, [false; 3]), [true, false, true]);
+
+        // Firing pyro 1 shorts its sense line, which doesn't read as losing the igniter
+        adc[0] = 40;
+        assert_eq!(
+            sensor.update(adc, [true, false, false]),
+            [true, false, true]
+        );
+        // Once it stops firing the real reading is used again
+        assert_eq!(sensor.update(adc, [false; 3]), [false, false, true]);
+
+        let mut workspace = DataWorkspace::new();
+        for reading in sensor.readings() {
+            workspace.update(reading);
+        }
+        assert_eq!(
+            workspace.get_object(CheckKind::Pyro1Continuity),
+            Some(ObjectState::Flag(false))
+        );
+        assert_eq!(
+            workspace.get_object(CheckKind::Pyro3Continuity),
+            Some(ObjectState::Flag(true))
+        );
+    }
+}
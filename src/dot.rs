@@ -0,0 +1,160 @@
+//! Exports a config as a Graphviz `dot` graph, so it can be reviewed visually before flight.
+//!
+//! Each state is a node, and each transition is an edge labeled with what triggers it. Aborts are
+//! drawn as dashed red edges, and terminal states as double circles.
+
+use crate::index::{ConfigFile, State, StateTransition};
+
+use std::fmt::Write;
+use std::string::String;
+
+/// Renders `config` as a `dot` digraph
+pub fn to_dot(config: &ConfigFile) -> String {
+    let mut out = String::new();
+    // Writing to a `String` cannot fail
+    let _ = write_dot(config, &mut out);
+    out
+}
+
+fn write_dot(config: &ConfigFile, out: &mut String) -> std::fmt::Result {
+    writeln!(out, "digraph config {{")?;
+    writeln!(out, "    start [shape=point];")?;
+    writeln!(out, "    start -> s{};", usize::from(config.default_state))?;
+
+    for (i, state) in config.states.iter().enumerate() {
+        let shape = if state.is_terminal {
+            "doublecircle"
+        } else {
+            "circle"
+        };
+        let label = crate::StateLabel {
+            id: i as u8,
+            name: state.name.as_deref(),
+        };
+        writeln!(
+            out,
+            "    s{} [label=\"{}\", shape={}];",
+            i,
+            escape(&label.to_string()),
+            shape
+        )?;
+    }
+
+    for (i, state) in config.states.iter().enumerate() {
+        for (check_idx, check) in state.checks.iter().enumerate() {
+            if let Some(transition) = check.transition {
+                write_edge(out, i, transition, &check_label(state, check_idx))?;
+            }
+        }
+        if let Some(timeout) = state.timeout {
+            let label = format!("timeout {}s", timeout.time);
+            write_edge(out, i, timeout.transition, &label)?;
+        }
+        if let Some(transition) = state.fault_transition {
+            write_edge(out, i, transition, "sensor fault")?;
+        }
+    }
+
+    writeln!(out, "}}")
+}
+
+fn write_edge(
+    out: &mut String,
+    from: usize,
+    transition: StateTransition,
+    label: &str,
+) -> std::fmt::Result {
+    let to = usize::from(transition.state());
+    match transition {
+        StateTransition::Transition(_) => {
+            writeln!(
+                out,
+                "    s{} -> s{} [label=\"{}\"];",
+                from,
+                to,
+                escape(label)
+            )
+        }
+        StateTransition::Abort(_, reason) => writeln!(
+            out,
+            "    s{} -> s{} [label=\"{}\\nabort: {:?}\", color=red, style=dashed];",
+            from,
+            to,
+            escape(label),
+            reason
+        ),
+    }
+}
+
+/// Labels the transition of the check at `check_idx`, which for a group is every condition in it
+fn check_label(state: &State, check_idx: usize) -> String {
+    let check = &state.checks[check_idx];
+    match check.group {
+        None => check.data.to_string(),
+        Some(group) => state
+            .checks
+            .iter()
+            .filter(|other| other.group == Some(group))
+            .map(|other| other.data.to_string())
+            .collect::<std::vec::Vec<_>>()
+            .join("\n&& "),
+    }
+}
+
+/// Escapes `label` to be written inside a quoted `dot` string
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_dot;
+    use crate::builder::{ConfigBuilder, StateBuilder, Transition};
+    use crate::{AbortReason, CheckData, FloatCondition, NativeFlagCondition};
+
+    #[test]
+    fn test_to_dot() {
+        let config = ConfigBuilder::new(0)
+            .state(
+                StateBuilder::new(0)
+                    .check(
+                        CheckData::Tilt(FloatCondition::GreaterThan(30.0)),
+                        Some(Transition::Abort(2, AbortReason::TiltExceeded)),
+                    )
+                    .timeout(5.0, Transition::To(1)),
+            )
+            .state(
+                StateBuilder::new(1)
+                    .all_of(
+                        &[
+                            CheckData::ApogeeFlag(NativeFlagCondition(true)),
+                            CheckData::Velocity(FloatCondition::LessThan(0.0)),
+                        ],
+                        Transition::To(2),
+                    )
+                    .fault_transition(Transition::Abort(2, AbortReason::SensorFault)),
+            )
+            .state(StateBuilder::new(2).name("Safe").terminal())
+            .build()
+            .unwrap();
+
+        let dot = to_dot(&config);
+        assert!(dot.starts_with("digraph config {\n"));
+        assert!(dot.ends_with("}\n"));
+
+        assert!(dot.contains("    start -> s0;\n"));
+        assert!(dot.contains("    s0 [label=\"0\", shape=circle];\n"));
+        assert!(dot.contains("    s2 [label=\"Safe (2)\", shape=doublecircle];\n"));
+
+        assert!(dot.contains(
+            "    s0 -> s2 [label=\"Tilt > 30.0°\\nabort: TiltExceeded\", \
+             color=red, style=dashed];\n"
+        ));
+        assert!(dot.contains("    s0 -> s1 [label=\"timeout 5s\"];\n"));
+        assert!(dot.contains("    s1 -> s2 [label=\"Apogee set\\n&& Velocity < 0.0 m/s\"];\n"));
+        assert!(dot.contains("    s1 -> s2 [label=\"sensor fault\\nabort: SensorFault\""));
+    }
+}
@@ -0,0 +1,353 @@
+//! Filters which smooth noisy sensor readings before they are used by checks. Like the detectors
+//! in [`crate::detection`], these only do the math, so data acquisition feeds them each new sample
+//! along with the time since the last one.
+
+/// Tuning for an [`AltitudeFilter`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct KalmanParams {
+    /// Variance of the unmodeled acceleration, in (m/s²)². Higher values trust the barometer more
+    pub acceleration_variance: f32,
+    /// Variance of the barometric altitude readings, in m². Higher values trust the barometer less
+    pub altitude_variance: f32,
+}
+
+impl Default for KalmanParams {
+    fn default() -> Self {
+        Self {
+            acceleration_variance: 1.0,
+            altitude_variance: 4.0,
+        }
+    }
+}
+
+/// A 1D Kalman filter, which fuses barometric altitude with vertical acceleration into smoothed
+/// estimates of altitude and velocity
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AltitudeFilter {
+    pub params: KalmanParams,
+    altitude: f32,
+    velocity: f32,
+    /// The covariance of the estimate, indexed by altitude then velocity
+    covariance: [[f32; 2]; 2],
+}
+
+impl AltitudeFilter {
+    /// Creates a filter at rest at `initial_altitude`
+    pub fn new(params: KalmanParams, initial_altitude: f32) -> Self {
+        Self {
+            params,
+            altitude: initial_altitude,
+            velocity: 0.0,
+            covariance: [[params.altitude_variance, 0.0], [0.0, 1.0]],
+        }
+    }
+
+    /// Updates the estimate with a barometric altitude in meters and a vertical acceleration in
+    /// m/s², positive up, taken `dt` seconds after the last sample.
+    ///
+    /// An invalid altitude is skipped so a single bad reading can't corrupt the estimate, and the
+    /// filter instead coasts on acceleration alone for that sample.
+    pub fn update(&mut self, measured_altitude: f32, acceleration: f32, dt: f32) {
+        self.predict(acceleration, dt);
+        if measured_altitude.is_finite() {
+            self.correct(measured_altitude);
+        }
+    }
+
+    /// Returns the estimated altitude in meters
+    pub fn altitude(&self) -> f32 {
+        self.altitude
+    }
+
+    /// Returns the estimated vertical velocity in m/s, positive up
+    pub fn velocity(&self) -> f32 {
+        self.velocity
+    }
+
+    fn predict(&mut self, acceleration: f32, dt: f32) {
+        let acceleration = if acceleration.is_finite() {
+            acceleration
+        } else {
+            0.0
+        };
+
+        self.altitude += self.velocity * dt + 0.5 * acceleration * dt * dt;
+        self.velocity += acceleration * dt;
+
+        // P = F P Fᵀ + Q, where F = [[1, dt], [0, 1]]
+        let [[p00, p01], [p10, p11]] = self.covariance;
+        let q = self.params.acceleration_variance;
+        let dt2 = dt * dt;
+        self.covariance = [
+            [
+                p00 + dt * (p10 + p01) + dt2 * p11 + q * dt2 * dt2 / 4.0,
+                p01 + dt * p11 + q * dt2 * dt / 2.0,
+            ],
+            [p10 + dt * p11 + q * dt2 * dt / 2.0, p11 + q * dt2],
+        ];
+    }
+
+    fn correct(&mut self, measured_altitude: f32) {
+        let [[p00, p01], [p10, p11]] = self.covariance;
+        let innovation = measured_altitude - self.altitude;
+        let gain = [
+            p00 / (p00 + self.params.altitude_variance),
+            p10 / (p00 + self.params.altitude_variance),
+        ];
+
+        self.altitude += gain[0] * innovation;
+        self.velocity += gain[1] * innovation;
+        self.covariance = [
+            [(1.0 - gain[0]) * p00, (1.0 - gain[0]) * p01],
+            [p10 - gain[1] * p00, p11 - gain[1] * p01],
+        ];
+    }
+}
+
+/// Averages the last `N` samples, to reduce noise on raw altitude before it is used
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MovingAverage<const N: usize> {
+    samples: [f32; N],
+    next: usize,
+    len: usize,
+}
+
+impl<const N: usize> MovingAverage<N> {
+    pub fn new() -> Self {
+        Self {
+            samples: [0.0; N],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    /// Adds `sample` to the window, returning the new average. Until `N` samples have been added
+    /// this averages only the samples seen so far.
+    ///
+    /// An invalid sample is skipped so it does not poison the window for the next `N` samples.
+    pub fn update(&mut self, sample: f32) -> Option<f32> {
+        if sample.is_finite() {
+            self.samples[self.next] = sample;
+            self.next = (self.next + 1) % N;
+            self.len = (self.len + 1).min(N);
+        }
+        self.value()
+    }
+
+    /// Returns the current average, or `None` before any sample has been added
+    pub fn value(&self) -> Option<f32> {
+        if self.len == 0 {
+            return None;
+        }
+        // The window is summed each time so rounding errors do not build up over a flight
+        let sum: f32 = self.samples[..self.len].iter().sum();
+        Some(sum / self.len as f32)
+    }
+}
+
+impl<const N: usize> Default for MovingAverage<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Estimates vertical velocity by differentiating barometric altitude, for when there is no
+/// accelerometer to run an [`AltitudeFilter`] with. Each new slope is blended into the estimate to
+/// reject barometer noise
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct VelocityEstimator {
+    /// The weight given to each new slope, from 0 to 1. Lower values smooth more but lag more, and
+    /// 1 uses the latest slope unsmoothed
+    pub smoothing: f32,
+    last_altitude: Option<f32>,
+    velocity: Option<f32>,
+}
+
+impl VelocityEstimator {
+    pub fn new(smoothing: f32) -> Self {
+        Self {
+            smoothing,
+            last_altitude: None,
+            velocity: None,
+        }
+    }
+
+    /// Updates the estimate with an altitude in meters taken `dt` seconds after the last one,
+    /// returning the velocity in m/s, positive up. This is 0 until there are two samples to
+    /// differentiate.
+    ///
+    /// An invalid altitude or `dt` is skipped, so the estimate holds until the next good sample.
+    pub fn update(&mut self, altitude: f32, dt: f32) -> f32 {
+        if !altitude.is_finite() || !dt.is_finite() || dt <= 0.0 {
+            return self.velocity();
+        }
+
+        if let Some(last_altitude) = self.last_altitude {
+            let slope = (altitude - last_altitude) / dt;
+            self.velocity = Some(match self.velocity {
+                // The first slope is taken as is, so the estimate doesn't ramp up from 0
+                None => slope,
+                Some(velocity) => velocity + self.smoothing * (slope - velocity),
+            });
+        }
+        self.last_altitude = Some(altitude);
+        self.velocity()
+    }
+
+    /// Returns the estimated vertical velocity in m/s, positive up
+    pub fn velocity(&self) -> f32 {
+        self.velocity.unwrap_or(0.0)
+    }
+}
+
+impl Default for VelocityEstimator {
+    fn default() -> Self {
+        Self::new(0.2)
+    }
+}
+
+/// Finds the ground pressure to pass to [`pressure_to_altitude`] by averaging the first valid
+/// barometer readings taken on the pad. Once enough readings have been seen the reference is
+/// fixed, so later readings taken during flight cannot move it
+///
+/// [`pressure_to_altitude`]: crate::conversions::pressure_to_altitude
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GroundCalibration {
+    samples: u16,
+    mean: f32,
+    len: u16,
+}
+
+impl GroundCalibration {
+    /// Creates a calibration which averages `samples` readings. At least one reading is always
+    /// taken, so a count of zero behaves as one
+    pub fn new(samples: u16) -> Self {
+        Self {
+            samples: samples.max(1),
+            mean: 0.0,
+            len: 0,
+        }
+    }
+
+    /// Adds a `pressure` reading in pascals, returning the ground pressure once enough readings
+    /// have been seen.
+    ///
+    /// An invalid reading is skipped so it does not poison the reference for the whole flight.
+    pub fn update(&mut self, pressure: f32) -> Option<f32> {
+        if self.len < self.samples && pressure.is_finite() && pressure > 0.0 {
+            self.len += 1;
+            // A running mean rather than a sum, as a sum of pressures loses precision in an f32
+            self.mean += (pressure - self.mean) / f32::from(self.len);
+        }
+        self.ground_pressure()
+    }
+
+    /// Returns the ground pressure in pascals, or `None` while still calibrating
+    pub fn ground_pressure(&self) -> Option<f32> {
+        (self.len >= self.samples).then_some(self.mean)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AltitudeFilter, GroundCalibration, KalmanParams, MovingAverage, VelocityEstimator,
+    };
+    use crate::conversions::pressure_to_altitude;
+
+    /// Deterministic noise in `-amplitude..amplitude`
+    fn noise(seed: &mut u32, amplitude: f32) -> f32 {
+        *seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        ((*seed >> 16) as f32 / 32_768.0 - 1.0) * amplitude
+    }
+
+    #[test]
+    fn test_altitude_filter() {
+        let mut filter = AltitudeFilter::new(KalmanParams::default(), 0.0);
+        let mut seed = 1;
+        let dt = 0.01;
+
+        // Coasting upwards at a steady 50 m/s, with noisy sensors
+        for i in 1..=1000 {
+            let altitude = 50.0 * i as f32 * dt;
+            filter.update(altitude + noise(&mut seed, 3.0), noise(&mut seed, 0.5), dt);
+        }
+
+        let velocity = filter.velocity();
+        assert!(velocity > 48.0 && velocity < 52.0, "velocity: {}", velocity);
+        let altitude_error = filter.altitude() - 500.0;
+        assert!(altitude_error > -2.0 && altitude_error < 2.0);
+
+        // A bad barometer reading is ignored
+        filter.update(f32::NAN, 0.0, dt);
+        assert!(filter.altitude().is_finite() && filter.velocity().is_finite());
+    }
+
+    #[test]
+    fn test_velocity_estimator() {
+        let mut estimator = VelocityEstimator::default();
+        let dt = 0.05;
+
+        // The first sample has nothing to differentiate against
+        assert_eq!(estimator.update(100.0, dt), 0.0);
+
+        // Climbing at a constant 40 m/s
+        for i in 1..=40 {
+            let velocity = estimator.update(100.0 + 40.0 * i as f32 * dt, dt);
+            assert!((velocity - 40.0).abs() < 0.01, "velocity: {}", velocity);
+        }
+
+        // Bad samples are skipped rather than read as a huge slope
+        assert!((estimator.update(f32::NAN, dt) - 40.0).abs() < 0.01);
+        assert!((estimator.update(1000.0, 0.0) - 40.0).abs() < 0.01);
+
+        // Noise is smoothed, so a single 5m spike moves the estimate by much less than 100 m/s
+        let velocity = estimator.update(100.0 + 40.0 * 41.0 * dt + 5.0, dt);
+        assert!(velocity < 70.0, "velocity: {}", velocity);
+    }
+
+    #[test]
+    fn test_moving_average() {
+        let mut filter = MovingAverage::<4>::new();
+        assert_eq!(filter.value(), None);
+
+        // Warming up averages only the samples seen so far
+        assert_eq!(filter.update(0.0), Some(0.0));
+        assert_eq!(filter.update(0.0), Some(0.0));
+        assert_eq!(filter.update(f32::NAN), Some(0.0));
+        assert_eq!(filter.update(0.0), Some(0.0));
+        assert_eq!(filter.update(0.0), Some(0.0));
+
+        // A step ramps up over the window
+        assert_eq!(filter.update(8.0), Some(2.0));
+        assert_eq!(filter.update(8.0), Some(4.0));
+        assert_eq!(filter.update(8.0), Some(6.0));
+        assert_eq!(filter.update(8.0), Some(8.0));
+        assert_eq!(filter.update(8.0), Some(8.0));
+    }
+
+    #[test]
+    fn test_ground_calibration() {
+        let mut calibration = GroundCalibration::new(4);
+
+        // Still calibrating, and a bad reading doesn't count towards the 4
+        assert_eq!(calibration.update(97_010.0), None);
+        assert_eq!(calibration.update(f32::NAN), None);
+        assert_eq!(calibration.update(96_990.0), None);
+        assert_eq!(calibration.update(97_005.0), None);
+        let ground = calibration.update(96_995.0).unwrap();
+        assert!((ground - 97_000.0).abs() < 0.1);
+
+        // Sitting on the pad now reads as 0 AGL
+        assert!(pressure_to_altitude(97_000.0, Some(ground)).abs() < 0.1);
+
+        // The reference doesn't drift once the rocket is climbing
+        assert_eq!(calibration.update(90_000.0), Some(ground));
+        assert!(pressure_to_altitude(90_000.0, Some(ground)) > 500.0);
+
+        // A calibration always takes at least one reading
+        let mut single = GroundCalibration::new(0);
+        assert_eq!(single.ground_pressure(), None);
+        assert_eq!(single.update(97_000.0), Some(97_000.0));
+    }
+}
@@ -0,0 +1,111 @@
+//! A Q16.16 fixed-point number, so checks can be evaluated deterministically and without an FPU.
+//!
+//! Only check evaluation is covered. [`AltitudeFilter`](crate::filter::AltitudeFilter) and
+//! [`MovingAverage`](crate::filter::MovingAverage) still run in `f32`, and their output is
+//! converted with [`Fixed::from_f32`] before being checked.
+
+use core::ops::{Neg, Sub};
+
+/// The number of fractional bits
+const FRACTIONAL_BITS: u32 = 16;
+const ONE: f32 = (1u32 << FRACTIONAL_BITS) as f32;
+
+/// A signed Q16.16 fixed-point number, covering about ±32768 with a resolution of 1/65536.
+///
+/// Arithmetic saturates at the ends of the range rather than wrapping.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Fixed(pub i32);
+
+impl Fixed {
+    /// Converts `value`, saturating finite values outside the range. Returns `None` for NaN and
+    /// infinities, as there is nothing sensible to compare them as
+    pub fn from_f32(value: f32) -> Option<Self> {
+        // `as` saturates out of range floats, but would also turn NaN into a valid looking zero
+        value.is_finite().then_some(Fixed((value * ONE) as i32))
+    }
+
+    pub fn to_f32(self) -> f32 {
+        self.0 as f32 / ONE
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Fixed(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Fixed(self.0.saturating_neg())
+    }
+}
+
+impl crate::Number for Fixed {
+    fn from_f32(value: f32) -> Option<Self> {
+        Fixed::from_f32(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fixed;
+    use crate::{FloatCondition, ObjectState};
+
+    #[test]
+    fn test_conversion() {
+        assert_eq!(Fixed::from_f32(1.5), Some(Fixed(0x0001_8000)));
+        assert_eq!(Fixed::from_f32(-2.25).map(Fixed::to_f32), Some(-2.25));
+        assert_eq!(Fixed::from_f32(1e9), Some(Fixed(i32::MAX)));
+        assert_eq!(Fixed::from_f32(f32::NAN), None);
+        assert_eq!(Fixed::from_f32(f32::NEG_INFINITY), None);
+    }
+
+    #[test]
+    fn test_conditions_match_float() {
+        let conditions = [
+            FloatCondition::LessThan(300.0),
+            FloatCondition::GreaterThan(-12.5),
+            FloatCondition::between(100.0, 200.0),
+            FloatCondition::EqualTo {
+                value: 7.4,
+                epsilon: 0.25,
+            },
+            FloatCondition::NotEqual {
+                value: 0.0,
+                epsilon: 0.5,
+            },
+        ];
+
+        // Every sample is well clear of the thresholds relative to the fixed-point resolution
+        let samples = [
+            -1000.0, -12.0, -0.1, 0.0, 7.3, 99.9, 150.0, 200.1, 299.0, 301.0,
+        ];
+        for condition in conditions {
+            for sample in samples {
+                assert_eq!(
+                    condition.is_satisfied_by(Fixed::from_f32(sample).unwrap()),
+                    condition.is_satisfied(sample),
+                    "{:?} with {}",
+                    condition,
+                    sample
+                );
+            }
+
+            // An invalid reading is rejected before either representation evaluates it, so it
+            // can't slip through as a zero in fixed point
+            for sample in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+                assert!(!ObjectState::Float(sample).is_valid());
+                assert_eq!(Fixed::from_f32(sample), None);
+            }
+        }
+
+        // A threshold that doesn't fit never matches, instead of being compared as zero
+        let condition = FloatCondition::LessThan(f32::NAN);
+        assert!(!condition.is_satisfied_by(Fixed::from_f32(-1.0).unwrap()));
+    }
+}
@@ -24,13 +24,36 @@ impl<T: StableDeref, const N: usize> FrozenVec<T, N> {
     const INIT: MaybeUninit<T> = MaybeUninit::uninit();
 
     /// Constructs a new, empty vector with a fixed capacity of `N`
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             buffer: UnsafeCell::new([Self::INIT; N]),
             len: UnsafeCell::new(0),
         }
     }
 
+    /// Constructs a vector holding `items`, which can be used to declare one in a `static`
+    ///
+    /// Panics if there are more than `N` items, which fails compilation when used in a `static`
+    pub const fn from_array<const M: usize>(items: [T; M]) -> Self {
+        assert!(M <= N, "too many items for the FrozenVec's capacity");
+
+        let items = MaybeUninit::new(items);
+        let items = items.as_ptr() as *const T;
+        let mut buffer = [Self::INIT; N];
+        let mut i = 0;
+        while i < M {
+            // # SAFETY: `items` holds `M` initialized elements, and each is read exactly once.
+            // `items` is never dropped, so every element is moved into the buffer
+            buffer[i] = MaybeUninit::new(unsafe { items.add(i).read() });
+            i += 1;
+        }
+
+        Self {
+            buffer: UnsafeCell::new(buffer),
+            len: UnsafeCell::new(M),
+        }
+    }
+
     /// Appends an `item` to the back of the collection
     ///
     /// Returns back the `item` if the vector is full
@@ -113,7 +136,7 @@ impl<T: StableDeref, const N: usize> FrozenVec<T, N> {
     }
 
     /// Returns an iterator over the vector.
-    pub fn iter(&self) -> Iter<T, N> {
+    pub fn iter(&self) -> Iter<'_, T, N> {
         self.into_iter()
     }
 
@@ -226,7 +249,7 @@ fn test_accessors() {
     let y = 2;
     let z = 4;
 
-    assert_eq!(vec.is_empty(), true);
+    assert!(vec.is_empty());
     assert_eq!(vec.len(), 0);
     // assert_eq!(vec.first(), None);
     // assert_eq!(vec.last(), None);
@@ -236,9 +259,24 @@ fn test_accessors() {
     vec.push(&y).unwrap();
     vec.push(&z).unwrap();
 
-    assert_eq!(vec.is_empty(), false);
+    assert!(!vec.is_empty());
     assert_eq!(vec.len(), 3);
     // assert_eq!(vec.first(), Some("a"));
     // assert_eq!(vec.last(), Some("c"));
     assert_eq!(vec.get(1), Some(&y));
 }
+
+#[test]
+fn test_from_array() {
+    let vec: FrozenVec<&u32, 4> = const { FrozenVec::from_array([&2, &4, &6]) };
+
+    assert_eq!(vec.len(), 3);
+    assert_eq!(
+        vec.iter().copied().collect::<heapless::Vec<_, 4>>(),
+        [2, 4, 6]
+    );
+
+    // Items can still be pushed up to the capacity
+    vec.push(&8).unwrap();
+    assert!(vec.is_full());
+}
@@ -0,0 +1,307 @@
+//! The line-based serial command interface used on the pad, shared between the firmware which
+//! parses commands and the ground tools which send them.
+
+use core::str::FromStr;
+
+use heapless::Vec;
+
+/// The longest command line accepted, not including the line ending
+pub const MAX_LINE_LEN: usize = 32;
+
+/// A command sent from the ground over serial
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GroundCommand {
+    /// Report the current state and sensor values
+    Status,
+    /// Dump the flight log
+    Dump,
+    /// Erase the flight log
+    Erase,
+    Arm,
+    Disarm,
+    /// Run the pyro continuity ground test
+    Test,
+}
+
+impl GroundCommand {
+    /// Returns the text used to send this command
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GroundCommand::Status => "status",
+            GroundCommand::Dump => "dump",
+            GroundCommand::Erase => "erase",
+            GroundCommand::Arm => "arm",
+            GroundCommand::Disarm => "disarm",
+            GroundCommand::Test => "test",
+        }
+    }
+}
+
+/// An error in a received command line
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The line was empty or only whitespace
+    Empty,
+    /// The line does not name a known command
+    UnknownCommand,
+    /// The line was longer than [`MAX_LINE_LEN`], so it was discarded
+    LineTooLong,
+    /// The line was not valid UTF-8
+    InvalidUtf8,
+}
+
+impl FromStr for GroundCommand {
+    type Err = ParseError;
+
+    /// Parses a command, ignoring case and surrounding whitespace
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Err(ParseError::Empty);
+        }
+
+        [
+            GroundCommand::Status,
+            GroundCommand::Dump,
+            GroundCommand::Erase,
+            GroundCommand::Arm,
+            GroundCommand::Disarm,
+            GroundCommand::Test,
+        ]
+        .into_iter()
+        .find(|command| command.as_str().eq_ignore_ascii_case(line))
+        .ok_or(ParseError::UnknownCommand)
+    }
+}
+
+/// Collects received bytes into lines, so commands can be parsed a byte at a time from the UART
+/// without blocking the control loop
+#[derive(Debug, Default)]
+pub struct LineBuffer {
+    line: Vec<u8, MAX_LINE_LEN>,
+    overflowed: bool,
+}
+
+impl LineBuffer {
+    pub fn new() -> Self {
+        Self {
+            line: Vec::new(),
+            overflowed: false,
+        }
+    }
+
+    /// Adds a received byte, returning the parsed command once a full line has been received
+    pub fn push(&mut self, byte: u8) -> Option<Result<GroundCommand, ParseError>> {
+        if byte != b'\n' && byte != b'\r' {
+            if self.line.push(byte).is_err() {
+                self.overflowed = true;
+            }
+            return None;
+        }
+
+        // The `\n` of a `\r\n` line ending ends an empty line, which is ignored
+        if self.line.is_empty() && !self.overflowed {
+            return None;
+        }
+
+        let result = if self.overflowed {
+            Err(ParseError::LineTooLong)
+        } else {
+            core::str::from_utf8(&self.line)
+                .map_err(|_| ParseError::InvalidUtf8)
+                .and_then(str::parse)
+        };
+        self.line.clear();
+        self.overflowed = false;
+        Some(result)
+    }
+}
+
+/// A snapshot of the flight computer, which is reported in reply to [`GroundCommand::Status`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StatusSnapshot {
+    /// The id of the current state
+    pub state_id: u8,
+    /// Seconds since the current state was entered
+    pub elapsed_in_state: f32,
+    pub armed: bool,
+    /// Meters above the ground
+    pub altitude: f32,
+    /// Meters per second, up positive
+    pub velocity: f32,
+    /// Meters per second squared, up positive
+    pub acceleration: f32,
+    /// Whether each pyro channel, from 1 to 3, has continuity
+    pub continuity: [bool; 3],
+}
+
+/// Formats the snapshot as a single line of space separated `key=value` fields, in a fixed order,
+/// so it can be parsed by a ground script
+impl core::fmt::Display for StatusSnapshot {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let [pyro1, pyro2, pyro3] = self.continuity.map(u8::from);
+        write!(
+            f,
+            "status state={} t={:.2} armed={} alt={:.1} vel={:.1} acc={:.1} cont={}{}{}",
+            self.state_id,
+            self.elapsed_in_state,
+            u8::from(self.armed),
+            self.altitude,
+            self.velocity,
+            self.acceleration,
+            pyro1,
+            pyro2,
+            pyro3
+        )
+    }
+}
+
+/// The result of the pyro continuity ground test, run by [`GroundCommand::Test`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ContinuityReport {
+    /// Whether each pyro channel, from 1 to 3, has an igniter connected
+    pub continuity: [bool; 3],
+}
+
+impl ContinuityReport {
+    /// Returns true if every channel has an igniter connected
+    pub fn all_connected(&self) -> bool {
+        self.continuity.iter().all(|connected| *connected)
+    }
+}
+
+/// Formats the report as a single line of `pyroN=ok` or `pyroN=open` fields
+impl core::fmt::Display for ContinuityReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "test")?;
+        for (i, connected) in self.continuity.iter().enumerate() {
+            let result = if *connected { "ok" } else { "open" };
+            write!(f, " pyro{}={}", i + 1, result)?;
+        }
+        Ok(())
+    }
+}
+
+/// An error that stopped the ground test from running
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GroundTestError {
+    /// The pyros are armed, so testing them could fire a charge
+    Armed,
+}
+
+/// Runs the continuity ground test, reading each pyro channel with `read_continuity`, which is
+/// given the channel number from 1 to 3.
+///
+/// This refuses to run while `armed`, so the channels are always de-energized when they are read.
+pub fn ground_test<F>(
+    armed: bool,
+    mut read_continuity: F,
+) -> Result<ContinuityReport, GroundTestError>
+where
+    F: FnMut(u8) -> bool,
+{
+    if armed {
+        return Err(GroundTestError::Armed);
+    }
+    Ok(ContinuityReport {
+        continuity: [read_continuity(1), read_continuity(2), read_continuity(3)],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ground_test, GroundCommand, GroundTestError, LineBuffer, ParseError, StatusSnapshot,
+    };
+    use core::fmt::Write;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("status".parse(), Ok(GroundCommand::Status));
+        assert_eq!("dump".parse(), Ok(GroundCommand::Dump));
+        assert_eq!("erase".parse(), Ok(GroundCommand::Erase));
+        assert_eq!("  ARM \r".parse(), Ok(GroundCommand::Arm));
+        assert_eq!("Disarm".parse(), Ok(GroundCommand::Disarm));
+        assert_eq!("test".parse(), Ok(GroundCommand::Test));
+
+        assert_eq!("".parse::<GroundCommand>(), Err(ParseError::Empty));
+        assert_eq!(
+            "launch".parse::<GroundCommand>(),
+            Err(ParseError::UnknownCommand)
+        );
+        assert_eq!(
+            "arm now".parse::<GroundCommand>(),
+            Err(ParseError::UnknownCommand)
+        );
+    }
+
+    #[test]
+    fn test_line_buffer() {
+        let mut buffer = LineBuffer::new();
+        let mut feed = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .filter_map(|byte| buffer.push(*byte))
+                .collect::<heapless::Vec<_, 4>>()
+        };
+
+        assert!(feed(b"sta").is_empty());
+        assert_eq!(feed(b"tus\r\n"), [Ok(GroundCommand::Status)]);
+        assert_eq!(
+            feed(b"arm\nbogus\n"),
+            [Ok(GroundCommand::Arm), Err(ParseError::UnknownCommand)]
+        );
+        assert_eq!(feed(&[0xFF, b'\n']), [Err(ParseError::InvalidUtf8)]);
+
+        let long = [b'a'; super::MAX_LINE_LEN + 1];
+        assert!(feed(&long).is_empty());
+        assert_eq!(
+            feed(b"\ndisarm\n"),
+            [Err(ParseError::LineTooLong), Ok(GroundCommand::Disarm)]
+        );
+    }
+
+    #[test]
+    fn test_status_line() {
+        let snapshot = StatusSnapshot {
+            state_id: 2,
+            elapsed_in_state: 1.5,
+            armed: true,
+            altitude: 1234.56,
+            velocity: -12.34,
+            acceleration: -9.81,
+            continuity: [true, false, true],
+        };
+
+        let mut line = heapless::String::<128>::new();
+        write!(line, "{}", snapshot).unwrap();
+        assert_eq!(
+            line,
+            "status state=2 t=1.50 armed=1 alt=1234.6 vel=-12.3 acc=-9.8 cont=101"
+        );
+    }
+
+    #[test]
+    fn test_ground_test() {
+        let mut reads = 0;
+        assert_eq!(
+            ground_test(true, |_| {
+                reads += 1;
+                true
+            }),
+            Err(GroundTestError::Armed)
+        );
+        assert_eq!(reads, 0);
+
+        // Only the drogue and main igniters are connected
+        let report = ground_test(false, |channel| channel != 3).unwrap();
+        assert_eq!(report.continuity, [true, true, false]);
+        assert!(!report.all_connected());
+
+        let mut line = heapless::String::<64>::new();
+        write!(line, "{}", report).unwrap();
+        assert_eq!(line, "test pyro1=ok pyro2=ok pyro3=open");
+
+        assert!(ground_test(false, |_| true).unwrap().all_connected());
+    }
+}
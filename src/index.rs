@@ -7,12 +7,69 @@ use heapless::Vec;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct ConfigFile {
     pub default_state: StateIndex,
     pub states: Vec<State, MAX_STATES>,
+
+    /// If set, pyro commands wait for continuity on their channel before firing. See
+    /// [`ContinuityGuard`]
+    pub continuity_guard: Option<ContinuityGuard>,
+}
+
+impl ConfigFile {
+    /// Returns a label for the state at `index` to use in logs, which includes its name if it has
+    /// one
+    pub fn state_label(&self, index: StateIndex) -> crate::StateLabel<'_> {
+        crate::StateLabel {
+            id: index.0,
+            name: self
+                .states
+                .get(usize::from(index))
+                .and_then(|state| state.name.as_deref()),
+        }
+    }
+
+    /// Creates a config from `states`, failing if there are more than [`MAX_STATES`]
+    pub fn try_new(default_state: StateIndex, states: &[State]) -> Result<Self, ConfigError> {
+        Ok(Self {
+            default_state,
+            states: Vec::from_slice(states).map_err(|_| ConfigError::TooManyStates)?,
+            continuity_guard: None,
+        })
+    }
+}
+
+/// Holds off every command that turns a pyro channel on until that channel has continuity, so a
+/// charge is never fired into an open igniter.
+///
+/// This applies to the whole config rather than to each command to keep the stored config within
+/// one page. A config with every slot filled leaves only a few bytes of the page free, so even a
+/// one byte flag on each of its 48 commands would not fit, let alone a guard on each.
+///
+/// A command that is still waiting `timeout` after it was due aborts to `abort_to` with
+/// [`AbortReason::ContinuityLoss`](crate::AbortReason::ContinuityLoss)
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ContinuityGuard {
+    pub timeout: crate::Seconds,
+    pub abort_to: StateIndex,
+}
+
+/// An error returned when a config does not fit within the fixed bounds of the index
+/// representation
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// There are more than [`MAX_STATES`] states
+    TooManyStates,
+    /// A state has more than [`MAX_CHECKS_PER_STATE`] checks
+    TooManyChecks,
+    /// A state has more than [`MAX_COMMANDS_PER_STATE`] commands
+    TooManyCommands,
 }
 
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(transparent)]
 /// The which references a particular state
 pub struct StateIndex(u8);
@@ -46,11 +103,19 @@ impl From<StateIndex> for usize {
 /// This should be things like Armed, Stage1, Stage2, Safe, etc.
 ///
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct State {
-    //pub name: String<16>,
+    /// A name which is shown in logs in place of this state's index, such as `Drogue`
+    pub name: Option<crate::StateName>,
     pub checks: Vec<Check, MAX_CHECKS_PER_STATE>,
     pub commands: Vec<Command, MAX_COMMANDS_PER_STATE>,
     pub timeout: Option<Timeout>,
+    /// The transition that is made when a check in this state reads an invalid sensor value
+    pub fault_transition: Option<StateTransition>,
+    /// The minimum time this state must be active for before any check or timeout can transition
+    pub min_dwell: crate::Seconds,
+    /// Marks this state as a deliberate final state, such as Safe or Landed, that is never left
+    pub is_terminal: bool,
 }
 
 impl State {
@@ -60,14 +125,42 @@ impl State {
         timeout: Option<Timeout>,
     ) -> Self {
         Self {
+            name: None,
             checks,
             commands,
             timeout,
+            fault_transition: None,
+            min_dwell: crate::Seconds(0.0),
+            is_terminal: false,
         }
     }
+
+    /// Creates a state from `checks` and `commands`, failing if there are more than
+    /// [`MAX_CHECKS_PER_STATE`] or [`MAX_COMMANDS_PER_STATE`] of them
+    pub fn try_new(
+        checks: &[Check],
+        commands: &[Command],
+        timeout: Option<Timeout>,
+    ) -> Result<Self, ConfigError> {
+        Ok(Self::new(
+            Vec::from_slice(checks).map_err(|_| ConfigError::TooManyChecks)?,
+            Vec::from_slice(commands).map_err(|_| ConfigError::TooManyCommands)?,
+            timeout,
+        ))
+    }
+
+    /// Returns every transition this state can make, from its checks, timeout, and fault transition
+    pub fn transitions(&self) -> impl Iterator<Item = &StateTransition> {
+        self.checks
+            .iter()
+            .filter_map(|check| check.transition.as_ref())
+            .chain(self.timeout.as_ref().map(|timeout| &timeout.transition))
+            .chain(self.fault_transition.as_ref())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Timeout {
     /// Time in seconds to wait before transitioning
     pub time: f32,
@@ -83,15 +176,31 @@ impl Timeout {
 
 /// A check within a state that is run every time the state is run
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Check {
     //pub name: String<16>,
     pub data: crate::CheckData,
     pub transition: Option<StateTransition>,
+    /// The number of consecutive executions this check must be satisfied for before it transitions
+    pub debounce: u8,
+    /// Checks in the same state that share a group must all be satisfied before any of them
+    /// transition
+    pub group: Option<u8>,
+    /// Checks with a higher priority are evaluated first, so their transition wins when several
+    /// checks are satisfied at once. Checks with the same priority keep the order they are listed
+    /// in
+    pub priority: u8,
 }
 
 impl Check {
     pub fn new(data: crate::CheckData, transition: Option<StateTransition>) -> Self {
-        Self { data, transition }
+        Self {
+            data,
+            transition,
+            debounce: 1,
+            group: None,
+            priority: 0,
+        }
     }
 }
 
@@ -100,34 +209,99 @@ impl Check {
 ///
 /// The enum values are the indexes of a state
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum StateTransition {
     /// Represents a safe transition to another state
     Transition(StateIndex),
     /// Represents an abort to a safer state if an abort condition was met
-    Abort(StateIndex),
+    Abort(StateIndex, crate::AbortReason),
+}
+
+impl StateTransition {
+    /// Returns the index of the state this transition goes to
+    pub fn state(&self) -> StateIndex {
+        match self {
+            StateTransition::Transition(s) | StateTransition::Abort(s, _) => *s,
+        }
+    }
 }
 
 /// An action that takes place at a specific time after the state containing this is entered
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Command {
     /// The object that this command will act upon
     pub object: crate::CommandObject,
 
     /// How long after the state activates to execute this command
     pub delay: crate::Seconds,
+
+    /// Whether this command executes again after it first executes. For
+    /// [`Repeat::Periodic`](crate::Repeat::Periodic) commands, `delay` is the interval between
+    /// executions
+    pub repeat: crate::Repeat,
+
+    /// For pyro commands, how long the channel stays on before automatically turning off. `None`
+    /// leaves it on until another command turns it off
+    pub pulse: Option<crate::Seconds>,
 }
 
 impl Command {
     pub fn new(object: crate::CommandObject, delay: crate::Seconds) -> Self {
-        Self { object, delay }
+        Self {
+            object,
+            delay,
+            repeat: crate::Repeat::Never,
+            pulse: None,
+        }
+    }
+
+    /// Returns false if this is a periodic command whose interval is not a positive number of
+    /// seconds, which would execute it on every execution of its state
+    pub fn has_valid_interval(&self) -> bool {
+        self.repeat != crate::Repeat::Periodic || (self.delay.0.is_finite() && self.delay.0 > 0.0)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{Check, Command, ConfigError, ConfigFile, State, StateIndex};
+    use crate::{
+        CheckData, CommandObject, NativeFlagCondition, Seconds, MAX_CHECKS_PER_STATE,
+        MAX_COMMANDS_PER_STATE, MAX_STATES,
+    };
+
     #[test]
     #[cfg(target_pointer_width = "32")]
     fn test() {
-        assert_eq!(core::mem::size_of::<crate::index::ConfigFile>(), 1608);
+        assert_eq!(core::mem::size_of::<crate::index::ConfigFile>(), 3028);
+    }
+
+    #[test]
+    fn test_count_bounds() {
+        let check = Check::new(CheckData::ApogeeFlag(NativeFlagCondition(true)), None);
+        let command = Command::new(CommandObject::Beacon(true), Seconds(0.0));
+        let checks: [Check; MAX_CHECKS_PER_STATE + 1] = core::array::from_fn(|_| check.clone());
+        let commands = [command; MAX_COMMANDS_PER_STATE + 1];
+
+        assert!(State::try_new(&checks[1..], &commands[1..], None).is_ok());
+        assert_eq!(
+            State::try_new(&checks, &commands[1..], None),
+            Err(ConfigError::TooManyChecks)
+        );
+        assert_eq!(
+            State::try_new(&checks[1..], &commands, None),
+            Err(ConfigError::TooManyCommands)
+        );
+
+        // # SAFETY: The default state is always pushed
+        let default_state = unsafe { StateIndex::new_unchecked(0) };
+        let state = State::try_new(&[], &[], None).unwrap();
+        let states: [State; MAX_STATES + 1] = core::array::from_fn(|_| state.clone());
+        assert!(ConfigFile::try_new(default_state, &states[1..]).is_ok());
+        assert_eq!(
+            ConfigFile::try_new(default_state, &states),
+            Err(ConfigError::TooManyStates)
+        );
     }
 }
@@ -2,50 +2,387 @@
 
 extern crate alloc;
 
+pub mod builder;
+pub mod clock;
+pub mod configs;
+pub mod controls;
 pub mod conversions;
+#[cfg(feature = "std")]
+pub mod decode;
+pub mod detection;
+#[cfg(feature = "std")]
+pub mod dot;
+pub mod filter;
+#[cfg(feature = "fixed-point")]
+pub mod fixed;
 pub mod frozen;
+pub mod ground;
 pub mod index;
+pub mod log;
+#[cfg(feature = "std")]
+pub mod mock;
 pub mod reference;
+#[cfg(feature = "std")]
+pub mod sim;
+pub mod storage;
+pub mod telemetry;
+pub mod validation;
+pub mod workspace;
+pub mod xmodem;
 
-pub use conversions::indices_to_refs;
+pub use conversions::{indices_to_refs, ConversionError};
+pub use validation::validate;
 
 pub const MAX_STATES: usize = 16;
 pub const MAX_CHECKS_PER_STATE: usize = 3;
 pub const MAX_COMMANDS_PER_STATE: usize = 3;
+/// The longest name, in bytes, that a state can be given. This is kept short so that a config
+/// with every state named still fits in a single flash page
+pub const MAX_STATE_NAME_LEN: usize = 10;
+
+/// The optional human readable name of a state, used only for logs and debugging
+pub type StateName = heapless::String<MAX_STATE_NAME_LEN>;
+
+/// Formats a state for logs as its name followed by its id, such as `Drogue (2)`, or as just its id
+/// if it has no name
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StateLabel<'a> {
+    pub id: u8,
+    pub name: Option<&'a str>,
+}
+
+impl core::fmt::Display for StateLabel<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.name {
+            Some(name) => write!(f, "{} ({})", name, self.id),
+            None => write!(f, "{}", self.id),
+        }
+    }
+}
+
+/// The slowest rate, in Hz, that the sensors can be sampled at by a [`CommandObject::DataRate`]
+pub const MIN_DATA_RATE_HZ: u16 = 10;
+/// The fastest rate, in Hz, that the sensors can be sampled at by a [`CommandObject::DataRate`]
+pub const MAX_DATA_RATE_HZ: u16 = 1000;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Seconds(pub f32);
 
+impl Seconds {
+    pub fn from_millis(millis: u32) -> Self {
+        Seconds(millis as f32 / 1000.0)
+    }
+
+    /// Returns this time in whole milliseconds, rounded to the nearest. Negative and NaN times are
+    /// zero
+    pub fn as_millis(self) -> u32 {
+        // `as` saturates, and turns NaN into zero
+        (self.0 * 1000.0 + 0.5) as u32
+    }
+}
+
+impl core::ops::Add for Seconds {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Seconds(self.0 + rhs.0)
+    }
+}
+
+impl core::ops::Sub for Seconds {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Seconds(self.0 - rhs.0)
+    }
+}
+
+impl From<core::time::Duration> for Seconds {
+    fn from(duration: core::time::Duration) -> Self {
+        Seconds(duration.as_secs_f32())
+    }
+}
+
+/// Negative and NaN times convert to a zero duration, as a `Duration` can't be negative
+impl From<Seconds> for core::time::Duration {
+    fn from(seconds: Seconds) -> Self {
+        core::time::Duration::try_from_secs_f32(seconds.0).unwrap_or(core::time::Duration::ZERO)
+    }
+}
+
+/// A distance in meters, which is the unit every altitude is stored and checked in
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Meters(pub f32);
+
+/// A distance in feet, which is only used to author configs and is converted to [`Meters`]
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Feet(pub f32);
+
 /// Describes the check for a `native' condition, I.E, a condition that the state machine emulates.
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct NativeFlagCondition(pub bool);
 
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct PyroContinuityCondition(pub bool);
 
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum FloatCondition {
     GreaterThan(f32),
     LessThan(f32),
-    Between { upper_bound: f32, lower_bound: f32 },
+    /// Satisfied when the value lies within `lower_bound..=upper_bound`. Use
+    /// [`FloatCondition::between`] to construct this so the bounds are always ordered
+    Between {
+        upper_bound: f32,
+        lower_bound: f32,
+    },
+    /// Satisfied when the value is within `epsilon` of `value`. A NaN reading is never equal
+    EqualTo {
+        value: f32,
+        epsilon: f32,
+    },
+    /// Satisfied when the value is further than `epsilon` from `value`, or is NaN
+    NotEqual {
+        value: f32,
+        epsilon: f32,
+    },
+}
+
+impl FloatCondition {
+    /// Creates a `Between` condition, swapping the bounds if they were given in the wrong order
+    pub fn between(lower_bound: f32, upper_bound: f32) -> Self {
+        if lower_bound <= upper_bound {
+            FloatCondition::Between {
+                upper_bound,
+                lower_bound,
+            }
+        } else {
+            FloatCondition::Between {
+                upper_bound: lower_bound,
+                lower_bound: upper_bound,
+            }
+        }
+    }
+
+    /// Returns this condition with every threshold, and the tolerance of `EqualTo` and `NotEqual`,
+    /// passed through `f`, such as to convert it to another unit
+    pub fn map<F: Fn(f32) -> f32>(self, f: F) -> Self {
+        match self {
+            FloatCondition::GreaterThan(threshold) => FloatCondition::GreaterThan(f(threshold)),
+            FloatCondition::LessThan(threshold) => FloatCondition::LessThan(f(threshold)),
+            FloatCondition::Between {
+                upper_bound,
+                lower_bound,
+            } => FloatCondition::between(f(lower_bound), f(upper_bound)),
+            FloatCondition::EqualTo { value, epsilon } => FloatCondition::EqualTo {
+                value: f(value),
+                epsilon: f(epsilon),
+            },
+            FloatCondition::NotEqual { value, epsilon } => FloatCondition::NotEqual {
+                value: f(value),
+                epsilon: f(epsilon),
+            },
+        }
+    }
+
+    /// Returns true if `value` satisfies this condition
+    pub fn is_satisfied(&self, value: f32) -> bool {
+        self.is_satisfied_by(value)
+    }
+
+    /// Returns true if `value` satisfies this condition, comparing in `value`'s representation so
+    /// the check can also be evaluated without floating point
+    ///
+    /// Always false if a threshold can't be represented in `N`.
+    pub fn is_satisfied_by<N: Number>(&self, value: N) -> bool {
+        self.evaluate(value).unwrap_or(false)
+    }
+
+    fn evaluate<N: Number>(&self, value: N) -> Option<bool> {
+        Some(match *self {
+            FloatCondition::GreaterThan(threshold) => value > N::from_f32(threshold)?,
+            FloatCondition::LessThan(threshold) => value < N::from_f32(threshold)?,
+            FloatCondition::Between {
+                upper_bound,
+                lower_bound,
+            } => value >= N::from_f32(lower_bound)? && value <= N::from_f32(upper_bound)?,
+            FloatCondition::EqualTo {
+                value: target,
+                epsilon,
+            } => approx_eq(value, N::from_f32(target)?, N::from_f32(epsilon)?),
+            FloatCondition::NotEqual {
+                value: target,
+                epsilon,
+            } => !approx_eq(value, N::from_f32(target)?, N::from_f32(epsilon)?),
+        })
+    }
+}
+
+/// A numeric representation that checks can be evaluated in
+pub trait Number:
+    Copy + PartialOrd + core::ops::Sub<Output = Self> + core::ops::Neg<Output = Self>
+{
+    /// Converts a threshold from a config into this representation, or `None` if it has no
+    /// equivalent
+    fn from_f32(value: f32) -> Option<Self>;
+}
+
+impl Number for f32 {
+    fn from_f32(value: f32) -> Option<Self> {
+        Some(value)
+    }
+}
+
+/// Returns true if `a` and `b` are within `epsilon` of each other. Always false if either is NaN
+fn approx_eq<N: Number>(a: N, b: N, epsilon: N) -> bool {
+    // `abs` is not available on `f32` in `core`, so compare against both sides instead
+    let difference = a - b;
+    difference <= epsilon && difference >= -epsilon
 }
 
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum CheckData {
     Altitude(FloatCondition),
+    /// Vertical velocity in m/s, positive up
+    Velocity(FloatCondition),
+    /// Vertical acceleration in m/s², positive up
+    Acceleration(FloatCondition),
+    /// Angle off vertical in degrees
+    Tilt(FloatCondition),
     ApogeeFlag(NativeFlagCondition),
     Pyro1Continuity(PyroContinuityCondition),
     Pyro2Continuity(PyroContinuityCondition),
     Pyro3Continuity(PyroContinuityCondition),
+    /// Set once the rocket has stayed on the ground and stopped moving, see
+    /// [`detection::LandingDetector`]
+    LandedFlag(NativeFlagCondition),
+    /// Set once the motor has burnt out, see [`detection::BurnoutDetector`]
+    BurnoutFlag(NativeFlagCondition),
+    /// Set while the rocket is moving near or above the speed of sound, see
+    /// [`detection::MachLockout`]
+    MachLockFlag(NativeFlagCondition),
+    /// Speed of descent in m/s, which is zero while ascending, see [`detection::descent_rate`]
+    DescentRate(FloatCondition),
+    /// Battery voltage in volts
+    BatteryVoltage(FloatCondition),
+    /// Seconds since the flight computer booted, regardless of which state is active
+    TimeSinceBoot(FloatCondition),
+    /// Seconds since liftoff was detected, which is never satisfied before liftoff, see
+    /// [`detection::LiftoffClock`]
+    TimeSinceLiftoff(FloatCondition),
+}
+
+impl CheckData {
+    /// Creates an `Altitude` check from a `condition` on the altitude in feet, converting its
+    /// thresholds to meters
+    pub fn altitude_in_feet(condition: FloatCondition) -> Self {
+        CheckData::Altitude(condition.map(|feet| Meters::from(Feet(feet)).0))
+    }
+
+    /// Returns the kind of object this check reads
+    pub fn kind(&self) -> CheckKind {
+        match self {
+            CheckData::Altitude(_) => CheckKind::Altitude,
+            CheckData::Velocity(_) => CheckKind::Velocity,
+            CheckData::Acceleration(_) => CheckKind::Acceleration,
+            CheckData::Tilt(_) => CheckKind::Tilt,
+            CheckData::ApogeeFlag(_) => CheckKind::ApogeeFlag,
+            CheckData::Pyro1Continuity(_) => CheckKind::Pyro1Continuity,
+            CheckData::Pyro2Continuity(_) => CheckKind::Pyro2Continuity,
+            CheckData::Pyro3Continuity(_) => CheckKind::Pyro3Continuity,
+            CheckData::LandedFlag(_) => CheckKind::LandedFlag,
+            CheckData::BurnoutFlag(_) => CheckKind::BurnoutFlag,
+            CheckData::MachLockFlag(_) => CheckKind::MachLockFlag,
+            CheckData::DescentRate(_) => CheckKind::DescentRate,
+            CheckData::BatteryVoltage(_) => CheckKind::BatteryVoltage,
+            CheckData::TimeSinceBoot(_) => CheckKind::TimeSinceBoot,
+            CheckData::TimeSinceLiftoff(_) => CheckKind::TimeSinceLiftoff,
+        }
+    }
+
+    /// Returns true if `value`, the current value of this check's [`CheckKind`], satisfies this
+    /// check's condition. A value of the wrong type never satisfies it
+    pub fn is_satisfied(&self, value: ObjectState) -> bool {
+        match (*self, value) {
+            (
+                CheckData::Altitude(condition)
+                | CheckData::Velocity(condition)
+                | CheckData::Acceleration(condition)
+                | CheckData::Tilt(condition)
+                | CheckData::DescentRate(condition)
+                | CheckData::BatteryVoltage(condition)
+                | CheckData::TimeSinceBoot(condition)
+                | CheckData::TimeSinceLiftoff(condition),
+                ObjectState::Float(value),
+            ) => condition.is_satisfied(value),
+            (
+                CheckData::ApogeeFlag(NativeFlagCondition(expected))
+                | CheckData::LandedFlag(NativeFlagCondition(expected))
+                | CheckData::BurnoutFlag(NativeFlagCondition(expected))
+                | CheckData::MachLockFlag(NativeFlagCondition(expected))
+                | CheckData::Pyro1Continuity(PyroContinuityCondition(expected))
+                | CheckData::Pyro2Continuity(PyroContinuityCondition(expected))
+                | CheckData::Pyro3Continuity(PyroContinuityCondition(expected)),
+                ObjectState::Flag(value),
+            ) => value == expected,
+            _ => false,
+        }
+    }
+}
+
+/// The object that a check reads, without the condition it is checked against
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CheckKind {
+    Altitude,
+    Velocity,
+    Acceleration,
+    Tilt,
+    ApogeeFlag,
+    Pyro1Continuity,
+    Pyro2Continuity,
+    Pyro3Continuity,
+    LandedFlag,
+    BurnoutFlag,
+    MachLockFlag,
+    DescentRate,
+    BatteryVoltage,
+    TimeSinceBoot,
+    TimeSinceLiftoff,
+}
+
+/// Why a state machine aborted to a safer state
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AbortReason {
+    /// A sensor returned an invalid or implausible reading
+    SensorFault,
+    /// A state timed out before its checks were satisfied
+    Timeout,
+    /// A pyro channel lost continuity
+    ContinuityLoss,
+    /// The rocket tilted too far off vertical
+    TiltExceeded,
+    /// The abort was requested by an operator
+    Manual,
+    /// The battery voltage sagged too low to safely continue
+    LowBattery,
 }
 
 /// Represents the state that something's value can be, this can be the value a command will set
 /// something to, or a value that a check will receive
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ObjectState {
-    /// An On/Off True/False for a GPIO for example
+    /// An On/Off True/False for a GPIO for example. Checks and commands match on this name, so
+    /// code expecting a `Bool` variant should be changed to `Flag`. Postcard stores variants by
+    /// index, so renaming one would not change the wire format, but the order must be kept
     Flag(bool),
     /// A floating-point value
     Float(f32),
@@ -53,12 +390,664 @@ pub enum ObjectState {
     Short(u16),
 }
 
+impl ObjectState {
+    /// Returns false if this is a NaN or infinite `Float`, which indicates a faulty sensor reading
+    /// that should not be compared against a [`FloatCondition`]
+    pub fn is_valid(&self) -> bool {
+        match self {
+            ObjectState::Float(value) => value.is_finite(),
+            ObjectState::Flag(_) | ObjectState::Short(_) => true,
+        }
+    }
+}
+
 /// An object that a command can act upon
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum CommandObject {
     Pyro1(bool),
     Pyro2(bool),
     Pyro3(bool),
     Beacon(bool),
     DataRate(u16),
+    /// Drives the servo on PWM `channel` to `angle_deg` degrees
+    Servo {
+        channel: u8,
+        angle_deg: f32,
+    },
+}
+
+impl CommandObject {
+    /// Returns the kind of object this command acts upon
+    pub fn kind(&self) -> CommandKind {
+        match self {
+            CommandObject::Pyro1(_) => CommandKind::Pyro1,
+            CommandObject::Pyro2(_) => CommandKind::Pyro2,
+            CommandObject::Pyro3(_) => CommandKind::Pyro3,
+            CommandObject::Beacon(_) => CommandKind::Beacon,
+            CommandObject::DataRate(_) => CommandKind::DataRate,
+            CommandObject::Servo { channel, .. } => CommandKind::Servo { channel: *channel },
+        }
+    }
+
+    /// Returns true if this command drives a pyro channel, and so must be blocked while disarmed
+    pub fn is_pyro(&self) -> bool {
+        self.kind().is_pyro()
+    }
+
+    /// Returns the continuity check for the channel this command fires, if it turns a pyro
+    /// channel on
+    pub fn continuity_check(&self) -> Option<CheckKind> {
+        match self {
+            CommandObject::Pyro1(true) => Some(CheckKind::Pyro1Continuity),
+            CommandObject::Pyro2(true) => Some(CheckKind::Pyro2Continuity),
+            CommandObject::Pyro3(true) => Some(CheckKind::Pyro3Continuity),
+            _ => None,
+        }
+    }
+
+    /// Returns false if this command sets a value the hardware cannot support, such as a
+    /// `DataRate` outside of [`MIN_DATA_RATE_HZ`] to [`MAX_DATA_RATE_HZ`]
+    pub fn is_supported(&self) -> bool {
+        match self {
+            CommandObject::DataRate(rate) => (MIN_DATA_RATE_HZ..=MAX_DATA_RATE_HZ).contains(rate),
+            _ => true,
+        }
+    }
+}
+
+/// When a command executes again after its first execution
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Repeat {
+    /// Execute at most once, even if the state is re-entered
+    Never,
+    /// Execute once each time the state is entered
+    OnReentry,
+    /// Execute every `delay` seconds while the state is active, measured from the last execution.
+    /// The period restarts each time the state is entered
+    Periodic,
+}
+
+/// The object that a command acts upon, without the value it is set to
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CommandKind {
+    Pyro1,
+    Pyro2,
+    Pyro3,
+    Beacon,
+    DataRate,
+    Servo { channel: u8 },
+}
+
+/// The error returned when a command is given a value of the wrong type for its object
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CommandTypeError {
+    /// The kind of command that was being created
+    pub kind: CommandKind,
+    /// The value it was given
+    pub value: ObjectState,
+}
+
+impl CommandKind {
+    /// Returns true if this is a pyro channel, and so must be blocked while disarmed
+    pub fn is_pyro(&self) -> bool {
+        matches!(
+            self,
+            CommandKind::Pyro1 | CommandKind::Pyro2 | CommandKind::Pyro3
+        )
+    }
+
+    /// Creates a command that sets this object to `value`
+    pub fn with_state(self, value: ObjectState) -> Result<CommandObject, CommandTypeError> {
+        match value {
+            ObjectState::Flag(value) => self.try_with_bool(value),
+            ObjectState::Short(value) => self.try_with_u16(value),
+            ObjectState::Float(value) => self.try_with_f32(value),
+        }
+    }
+
+    /// Creates a command that sets this object to the floating-point `value`
+    pub fn try_with_f32(self, value: f32) -> Result<CommandObject, CommandTypeError> {
+        match self {
+            CommandKind::Servo { channel } => Ok(CommandObject::Servo {
+                channel,
+                angle_deg: value,
+            }),
+            CommandKind::Pyro1
+            | CommandKind::Pyro2
+            | CommandKind::Pyro3
+            | CommandKind::Beacon
+            | CommandKind::DataRate => Err(CommandTypeError {
+                kind: self,
+                value: ObjectState::Float(value),
+            }),
+        }
+    }
+
+    /// Creates a command that sets this object to the boolean `value`
+    pub fn try_with_bool(self, value: bool) -> Result<CommandObject, CommandTypeError> {
+        match self {
+            CommandKind::Pyro1 => Ok(CommandObject::Pyro1(value)),
+            CommandKind::Pyro2 => Ok(CommandObject::Pyro2(value)),
+            CommandKind::Pyro3 => Ok(CommandObject::Pyro3(value)),
+            CommandKind::Beacon => Ok(CommandObject::Beacon(value)),
+            CommandKind::DataRate | CommandKind::Servo { .. } => Err(CommandTypeError {
+                kind: self,
+                value: ObjectState::Flag(value),
+            }),
+        }
+    }
+
+    /// Creates a command that sets this object to the integer `value`
+    pub fn try_with_u16(self, value: u16) -> Result<CommandObject, CommandTypeError> {
+        match self {
+            CommandKind::DataRate => Ok(CommandObject::DataRate(value)),
+            CommandKind::Pyro1
+            | CommandKind::Pyro2
+            | CommandKind::Pyro3
+            | CommandKind::Beacon
+            | CommandKind::Servo { .. } => Err(CommandTypeError {
+                kind: self,
+                value: ObjectState::Short(value),
+            }),
+        }
+    }
+}
+
+impl core::fmt::Display for FloatCondition {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FloatCondition::GreaterThan(threshold) => write!(f, "> {:?}", threshold),
+            FloatCondition::LessThan(threshold) => write!(f, "< {:?}", threshold),
+            FloatCondition::Between {
+                upper_bound,
+                lower_bound,
+            } => write!(f, "between {:?} and {:?}", lower_bound, upper_bound),
+            FloatCondition::EqualTo { value, epsilon } => {
+                write!(f, "= {:?} ± {:?}", value, epsilon)
+            }
+            FloatCondition::NotEqual { value, epsilon } => {
+                write!(f, "!= {:?} ± {:?}", value, epsilon)
+            }
+        }
+    }
+}
+
+/// Formats checks as the object, the condition, then its unit, such as `Altitude < 300.0 m`
+impl core::fmt::Display for CheckData {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let flag = |f: &mut core::fmt::Formatter<'_>, name, NativeFlagCondition(set)| {
+            write!(f, "{} {}", name, if set { "set" } else { "clear" })
+        };
+        let continuity = |f: &mut core::fmt::Formatter<'_>, name, PyroContinuityCondition(has)| {
+            write!(
+                f,
+                "{} {}",
+                name,
+                if has { "continuity" } else { "no continuity" }
+            )
+        };
+
+        match *self {
+            CheckData::Altitude(condition) => write!(f, "Altitude {} m", condition),
+            CheckData::Velocity(condition) => write!(f, "Velocity {} m/s", condition),
+            CheckData::Acceleration(condition) => write!(f, "Acceleration {} m/s²", condition),
+            CheckData::Tilt(condition) => write!(f, "Tilt {}°", condition),
+            CheckData::ApogeeFlag(condition) => flag(f, "Apogee", condition),
+            CheckData::Pyro1Continuity(condition) => continuity(f, "Pyro1", condition),
+            CheckData::Pyro2Continuity(condition) => continuity(f, "Pyro2", condition),
+            CheckData::Pyro3Continuity(condition) => continuity(f, "Pyro3", condition),
+            CheckData::LandedFlag(condition) => flag(f, "Landed", condition),
+            CheckData::BurnoutFlag(condition) => flag(f, "Burnout", condition),
+            CheckData::MachLockFlag(condition) => flag(f, "MachLock", condition),
+            CheckData::DescentRate(condition) => write!(f, "DescentRate {} m/s", condition),
+            CheckData::BatteryVoltage(condition) => write!(f, "BatteryVoltage {} V", condition),
+            CheckData::TimeSinceBoot(condition) => write!(f, "TimeSinceBoot {} s", condition),
+            CheckData::TimeSinceLiftoff(condition) => {
+                write!(f, "TimeSinceLiftoff {} s", condition)
+            }
+        }
+    }
+}
+
+impl core::fmt::Display for CommandKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CommandKind::Pyro1 => write!(f, "Pyro1"),
+            CommandKind::Pyro2 => write!(f, "Pyro2"),
+            CommandKind::Pyro3 => write!(f, "Pyro3"),
+            CommandKind::Beacon => write!(f, "Beacon"),
+            CommandKind::DataRate => write!(f, "DataRate"),
+            CommandKind::Servo { channel } => write!(f, "Servo{}", channel),
+        }
+    }
+}
+
+/// Formats commands as the object followed by the value it is set to, such as `Pyro1 ON`
+impl core::fmt::Display for CommandObject {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let kind = self.kind();
+        match *self {
+            CommandObject::Pyro1(on)
+            | CommandObject::Pyro2(on)
+            | CommandObject::Pyro3(on)
+            | CommandObject::Beacon(on) => {
+                write!(f, "{} {}", kind, if on { "ON" } else { "OFF" })
+            }
+            CommandObject::DataRate(rate) => write!(f, "{} {} Hz", kind, rate),
+            CommandObject::Servo { angle_deg, .. } => write!(f, "{} {:?}°", kind, angle_deg),
+        }
+    }
+}
+
+/// Extracts the value a command sets its object to
+impl From<CommandObject> for ObjectState {
+    fn from(object: CommandObject) -> Self {
+        match object {
+            CommandObject::Pyro1(value)
+            | CommandObject::Pyro2(value)
+            | CommandObject::Pyro3(value)
+            | CommandObject::Beacon(value) => ObjectState::Flag(value),
+            CommandObject::DataRate(value) => ObjectState::Short(value),
+            CommandObject::Servo { angle_deg, .. } => ObjectState::Float(angle_deg),
+        }
+    }
+}
+
+/// Creates a command from its kind and the value it sets, the same as [`CommandKind::with_state`]
+impl TryFrom<(CommandKind, ObjectState)> for CommandObject {
+    type Error = CommandTypeError;
+
+    fn try_from((kind, value): (CommandKind, ObjectState)) -> Result<Self, Self::Error> {
+        kind.with_state(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        CheckData, CheckKind, CommandKind, CommandObject, CommandTypeError, FloatCondition,
+        NativeFlagCondition, ObjectState, PyroContinuityCondition, Seconds,
+    };
+    use core::fmt::Write;
+
+    #[test]
+    fn test_between() {
+        let condition = FloatCondition::between(100.0, 200.0);
+
+        assert!(!condition.is_satisfied(50.0));
+        assert!(condition.is_satisfied(100.0));
+        assert!(condition.is_satisfied(150.0));
+        assert!(condition.is_satisfied(200.0));
+        assert!(!condition.is_satisfied(250.0));
+    }
+
+    #[test]
+    fn test_equal_to() {
+        let condition = FloatCondition::EqualTo {
+            value: 100.0,
+            epsilon: 0.5,
+        };
+
+        assert!(condition.is_satisfied(100.0));
+        assert!(condition.is_satisfied(99.5));
+        assert!(condition.is_satisfied(100.5));
+        assert!(!condition.is_satisfied(98.75));
+        assert!(!condition.is_satisfied(100.75));
+        assert!(!condition.is_satisfied(f32::NAN));
+    }
+
+    #[test]
+    fn test_not_equal() {
+        let condition = FloatCondition::NotEqual {
+            value: 100.0,
+            epsilon: 0.5,
+        };
+
+        assert!(!condition.is_satisfied(100.0));
+        assert!(!condition.is_satisfied(99.5));
+        assert!(!condition.is_satisfied(100.5));
+        assert!(condition.is_satisfied(98.75));
+        assert!(condition.is_satisfied(100.75));
+        assert!(condition.is_satisfied(f32::NAN));
+    }
+
+    #[test]
+    fn test_equality_wire_format() {
+        let conditions = [
+            (
+                FloatCondition::EqualTo {
+                    value: 100.0,
+                    epsilon: 0.5,
+                },
+                [0x03, 0x00, 0x00, 0xc8, 0x42, 0x00, 0x00, 0x00, 0x3f],
+            ),
+            (
+                FloatCondition::NotEqual {
+                    value: 100.0,
+                    epsilon: 0.5,
+                },
+                [0x04, 0x00, 0x00, 0xc8, 0x42, 0x00, 0x00, 0x00, 0x3f],
+            ),
+        ];
+
+        for (condition, expected) in conditions {
+            let mut buf = [0u8; 16];
+            let bytes = postcard::to_slice(&condition, &mut buf).unwrap();
+            assert_eq!(bytes, &expected[..]);
+            assert_eq!(postcard::from_bytes::<FloatCondition>(bytes), Ok(condition));
+
+            // The same bytes follow the CheckData variant tag when stored in a check
+            let check = CheckData::BatteryVoltage(condition);
+            let mut buf = [0u8; 16];
+            let bytes = postcard::to_slice(&check, &mut buf).unwrap();
+            assert_eq!(bytes[0], 12);
+            assert_eq!(&bytes[1..], &expected[..]);
+            assert_eq!(postcard::from_bytes::<CheckData>(bytes), Ok(check));
+        }
+    }
+
+    #[test]
+    fn test_velocity_descent() {
+        let check = CheckData::Velocity(FloatCondition::LessThan(0.0));
+        assert_eq!(check.kind(), CheckKind::Velocity);
+
+        assert!(!check.is_satisfied(ObjectState::Float(35.0)));
+        assert!(!check.is_satisfied(ObjectState::Float(0.0)));
+        assert!(check.is_satisfied(ObjectState::Float(-4.5)));
+    }
+
+    #[test]
+    fn test_acceleration_boost() {
+        let liftoff = CheckData::Acceleration(FloatCondition::GreaterThan(30.0));
+        let burnout = CheckData::Acceleration(FloatCondition::LessThan(0.0));
+        assert_eq!(liftoff.kind(), CheckKind::Acceleration);
+        assert_eq!(burnout.kind(), CheckKind::Acceleration);
+
+        // Sitting on the pad, boosting, then coasting after burnout
+        let profile = [0.1, -0.2, 12.0, 45.0, 80.0, 75.0, 20.0, -9.0, -9.5];
+        let first_liftoff = profile
+            .iter()
+            .position(|&a| liftoff.is_satisfied(ObjectState::Float(a)));
+        let first_burnout = profile
+            .iter()
+            .skip(first_liftoff.unwrap())
+            .position(|&a| burnout.is_satisfied(ObjectState::Float(a)))
+            .map(|i| i + first_liftoff.unwrap());
+
+        assert_eq!(first_liftoff, Some(3));
+        assert_eq!(first_burnout, Some(7));
+    }
+
+    #[test]
+    fn test_time_since_boot() {
+        let check = CheckData::TimeSinceBoot(FloatCondition::GreaterThan(8.0));
+        assert_eq!(check.kind(), CheckKind::TimeSinceBoot);
+
+        let first_fired = (0..200)
+            .map(|tick| tick as f32 * 0.1)
+            .find(|&elapsed| check.is_satisfied(ObjectState::Float(elapsed)));
+        assert!(matches!(first_fired, Some(t) if t > 8.0 && t < 8.15));
+    }
+
+    #[test]
+    fn test_seconds() {
+        assert!(Seconds(0.5) < Seconds(1.5));
+        assert!(Seconds(2.0) >= Seconds(2.0));
+        assert_eq!(Seconds(1.0) + Seconds(0.25), Seconds(1.25));
+        assert_eq!(Seconds(1.0) - Seconds(0.25), Seconds(0.75));
+
+        assert_eq!(Seconds::from_millis(1500), Seconds(1.5));
+        assert_eq!(Seconds(1.5).as_millis(), 1500);
+        assert_eq!(Seconds(0.0125).as_millis(), 13);
+        assert_eq!(Seconds::from_millis(25).as_millis(), 25);
+        assert_eq!(Seconds(-1.0).as_millis(), 0);
+        assert_eq!(Seconds(f32::NAN).as_millis(), 0);
+
+        let duration = core::time::Duration::from_millis(2250);
+        assert_eq!(Seconds::from(duration), Seconds(2.25));
+        assert_eq!(core::time::Duration::from(Seconds(2.25)), duration);
+        assert_eq!(
+            core::time::Duration::from(Seconds(-3.0)),
+            core::time::Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_check_is_satisfied() {
+        let check = CheckData::Altitude(FloatCondition::LessThan(300.0));
+        assert!(check.is_satisfied(ObjectState::Float(299.0)));
+        assert!(!check.is_satisfied(ObjectState::Float(301.0)));
+        assert!(!check.is_satisfied(ObjectState::Flag(true)));
+
+        let check = CheckData::Pyro2Continuity(PyroContinuityCondition(false));
+        assert!(check.is_satisfied(ObjectState::Flag(false)));
+        assert!(!check.is_satisfied(ObjectState::Flag(true)));
+        assert!(!check.is_satisfied(ObjectState::Short(0)));
+
+        let check = CheckData::ApogeeFlag(NativeFlagCondition(true));
+        assert!(check.is_satisfied(ObjectState::Flag(true)));
+        assert!(!check.is_satisfied(ObjectState::Float(1.0)));
+    }
+
+    #[test]
+    fn test_command_kind_with_state() {
+        assert_eq!(
+            CommandKind::Pyro2.with_state(ObjectState::Flag(true)),
+            Ok(CommandObject::Pyro2(true))
+        );
+        assert_eq!(
+            CommandKind::DataRate.with_state(ObjectState::Short(50)),
+            Ok(CommandObject::DataRate(50))
+        );
+
+        for kind in [CommandKind::Pyro1, CommandKind::DataRate] {
+            let object = kind
+                .with_state(ObjectState::Flag(true))
+                .or_else(|_| kind.with_state(ObjectState::Short(1)))
+                .unwrap();
+            assert_eq!(object.kind(), kind);
+        }
+    }
+
+    #[test]
+    fn test_servo_command() {
+        let kind = CommandKind::Servo { channel: 2 };
+        let object = kind.with_state(ObjectState::Float(45.0)).unwrap();
+
+        assert_eq!(
+            object,
+            CommandObject::Servo {
+                channel: 2,
+                angle_deg: 45.0
+            }
+        );
+        assert_eq!(object.kind(), kind);
+
+        assert!(kind.with_state(ObjectState::Flag(true)).is_err());
+        assert!(kind.with_state(ObjectState::Short(45)).is_err());
+    }
+
+    #[test]
+    fn test_is_pyro() {
+        assert!(CommandObject::Pyro1(true).is_pyro());
+        assert!(CommandObject::Pyro2(false).is_pyro());
+        assert!(CommandObject::Pyro3(true).is_pyro());
+
+        assert!(!CommandObject::Beacon(true).is_pyro());
+        assert!(!CommandObject::DataRate(20).is_pyro());
+        assert!(!CommandObject::Servo {
+            channel: 0,
+            angle_deg: 10.0
+        }
+        .is_pyro());
+    }
+
+    #[test]
+    fn test_object_state_round_trip() {
+        let objects = [
+            CommandObject::Pyro1(true),
+            CommandObject::Pyro2(false),
+            CommandObject::Pyro3(true),
+            CommandObject::Beacon(false),
+            CommandObject::DataRate(100),
+            CommandObject::Servo {
+                channel: 3,
+                angle_deg: -15.0,
+            },
+        ];
+
+        for object in objects {
+            let state = ObjectState::from(object);
+            assert_eq!(object.kind().with_state(state), Ok(object));
+            assert_eq!(CommandObject::try_from((object.kind(), state)), Ok(object));
+        }
+
+        assert_eq!(
+            ObjectState::from(CommandObject::Pyro1(true)),
+            ObjectState::Flag(true)
+        );
+        assert_eq!(
+            ObjectState::from(CommandObject::DataRate(20)),
+            ObjectState::Short(20)
+        );
+        assert!(CommandObject::try_from((CommandKind::Beacon, ObjectState::Short(1))).is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        fn render(value: impl core::fmt::Display) -> heapless::String<64> {
+            let mut s = heapless::String::new();
+            write!(s, "{}", value).unwrap();
+            s
+        }
+
+        assert_eq!(
+            render(CheckData::Altitude(FloatCondition::LessThan(300.0))),
+            "Altitude < 300.0 m"
+        );
+        assert_eq!(
+            render(CheckData::Velocity(FloatCondition::between(-5.0, 5.0))),
+            "Velocity between -5.0 and 5.0 m/s"
+        );
+        assert_eq!(
+            render(CheckData::Tilt(FloatCondition::GreaterThan(30.0))),
+            "Tilt > 30.0°"
+        );
+        assert_eq!(
+            render(CheckData::BatteryVoltage(FloatCondition::EqualTo {
+                value: 7.4,
+                epsilon: 0.5
+            })),
+            "BatteryVoltage = 7.4 ± 0.5 V"
+        );
+        assert_eq!(
+            render(CheckData::ApogeeFlag(NativeFlagCondition(true))),
+            "Apogee set"
+        );
+        assert_eq!(
+            render(CheckData::Pyro2Continuity(PyroContinuityCondition(false))),
+            "Pyro2 no continuity"
+        );
+
+        assert_eq!(render(CommandObject::Pyro1(true)), "Pyro1 ON");
+        assert_eq!(render(CommandObject::Beacon(false)), "Beacon OFF");
+        assert_eq!(render(CommandObject::DataRate(50)), "DataRate 50 Hz");
+        assert_eq!(
+            render(CommandObject::Servo {
+                channel: 2,
+                angle_deg: 45.0
+            }),
+            "Servo2 45.0°"
+        );
+        assert_eq!(render(CommandKind::Pyro3), "Pyro3");
+    }
+
+    #[cfg(feature = "defmt")]
+    #[test]
+    fn test_defmt_format() {
+        fn assert_format<T: defmt::Format>() {}
+
+        assert_format::<CheckData>();
+        assert_format::<CheckKind>();
+        assert_format::<CommandObject>();
+        assert_format::<CommandKind>();
+        assert_format::<CommandTypeError>();
+        assert_format::<ObjectState>();
+        assert_format::<crate::AbortReason>();
+        assert_format::<crate::index::ConfigFile>();
+    }
+
+    #[test]
+    fn test_command_kind_mismatches() {
+        assert_eq!(
+            CommandKind::DataRate.try_with_bool(true),
+            Err(CommandTypeError {
+                kind: CommandKind::DataRate,
+                value: ObjectState::Flag(true)
+            })
+        );
+
+        for kind in [
+            CommandKind::Pyro1,
+            CommandKind::Pyro2,
+            CommandKind::Pyro3,
+            CommandKind::Beacon,
+        ] {
+            assert_eq!(
+                kind.try_with_u16(20),
+                Err(CommandTypeError {
+                    kind,
+                    value: ObjectState::Short(20)
+                })
+            );
+        }
+
+        assert_eq!(
+            CommandKind::Pyro3.with_state(ObjectState::Float(1.0)),
+            Err(CommandTypeError {
+                kind: CommandKind::Pyro3,
+                value: ObjectState::Float(1.0)
+            })
+        );
+        assert_eq!(
+            CommandKind::DataRate.try_with_f32(20.0),
+            Err(CommandTypeError {
+                kind: CommandKind::DataRate,
+                value: ObjectState::Float(20.0)
+            })
+        );
+        assert_eq!(
+            CommandKind::Beacon.with_state(ObjectState::Short(3)),
+            Err(CommandTypeError {
+                kind: CommandKind::Beacon,
+                value: ObjectState::Short(3)
+            })
+        );
+    }
+
+    #[test]
+    fn test_object_state_validity() {
+        assert!(ObjectState::Float(120.0).is_valid());
+        assert!(ObjectState::Flag(false).is_valid());
+        assert!(ObjectState::Short(20).is_valid());
+
+        assert!(!ObjectState::Float(f32::NAN).is_valid());
+        assert!(!ObjectState::Float(f32::INFINITY).is_valid());
+        assert!(!ObjectState::Float(f32::NEG_INFINITY).is_valid());
+    }
+
+    #[test]
+    fn test_between_swaps_bounds() {
+        assert_eq!(
+            FloatCondition::between(200.0, 100.0),
+            FloatCondition::Between {
+                upper_bound: 200.0,
+                lower_bound: 100.0
+            }
+        );
+    }
 }
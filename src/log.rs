@@ -0,0 +1,709 @@
+//! A fixed binary format for timestamped flight log records, which can be written in `no_std` and
+//! decoded again by host tools.
+//!
+//! Every record starts with a header, followed by a payload whose size is fixed by its kind:
+//!
+//! | Bytes | Contents                                                  |
+//! |-------|-----------------------------------------------------------|
+//! | 1     | The [`RecordKind`]                                        |
+//! | 4     | Milliseconds since boot, little endian                    |
+//! | N     | The payload, [`RecordKind::payload_size`] bytes long      |
+//!
+//! All multi-byte values are little endian.
+
+use crate::{AbortReason, CommandObject};
+
+const HEADER_SIZE: usize = 5;
+
+/// The size of the largest encoded record, which is enough space to encode any record
+pub const MAX_RECORD_SIZE: usize = HEADER_SIZE + 6 + PANIC_MESSAGE_SIZE + PANIC_FILE_SIZE;
+
+/// The most bytes of a panic message that are kept in a [`RecordPayload::Panic`]
+pub const PANIC_MESSAGE_SIZE: usize = 24;
+
+/// The most bytes of the path of the file that panicked that are kept in a
+/// [`RecordPayload::Panic`]
+pub const PANIC_FILE_SIZE: usize = 16;
+
+/// An error encountered while encoding or decoding a log record
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LogError {
+    /// The buffer is too small to hold the record
+    BufferTooSmall,
+    /// The record kind byte does not name a known [`RecordKind`]
+    UnknownKind(u8),
+    /// The payload contains a value that does not decode, such as an unknown command object
+    InvalidPayload,
+}
+
+/// The kinds of record in a flight log
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RecordKind {
+    StateTransition,
+    SensorSample,
+    CommandExecuted,
+    Panic,
+}
+
+impl RecordKind {
+    /// Returns the size of the payload which follows the header of a record of this kind
+    pub fn payload_size(&self) -> usize {
+        match self {
+            RecordKind::StateTransition => 3,
+            RecordKind::SensorSample => 16,
+            RecordKind::CommandExecuted => 6,
+            RecordKind::Panic => 6 + PANIC_MESSAGE_SIZE + PANIC_FILE_SIZE,
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            RecordKind::StateTransition => 0,
+            RecordKind::SensorSample => 1,
+            RecordKind::CommandExecuted => 2,
+            RecordKind::Panic => 3,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self, LogError> {
+        match byte {
+            0 => Ok(RecordKind::StateTransition),
+            1 => Ok(RecordKind::SensorSample),
+            2 => Ok(RecordKind::CommandExecuted),
+            3 => Ok(RecordKind::Panic),
+            _ => Err(LogError::UnknownKind(byte)),
+        }
+    }
+}
+
+/// The contents of a log record
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RecordPayload {
+    /// The state machine moved from the state at index `from` to the state at index `to`
+    StateTransition {
+        from: u8,
+        to: u8,
+        /// Set if the transition was an abort
+        abort: Option<AbortReason>,
+    },
+    /// A sample of the values used by checks
+    SensorSample {
+        /// Meters above the ground
+        altitude: f32,
+        /// Meters per second, up positive
+        velocity: f32,
+        /// Meters per second squared
+        acceleration: f32,
+        /// Degrees from vertical
+        tilt: f32,
+    },
+    /// A command was executed
+    CommandExecuted(CommandObject),
+    /// The firmware panicked at `line` of `file`
+    Panic {
+        file: PanicFile,
+        line: u32,
+        message: PanicMessage,
+    },
+}
+
+impl RecordPayload {
+    /// Returns the kind of record this payload is stored as
+    pub fn kind(&self) -> RecordKind {
+        match self {
+            RecordPayload::StateTransition { .. } => RecordKind::StateTransition,
+            RecordPayload::SensorSample { .. } => RecordKind::SensorSample,
+            RecordPayload::CommandExecuted(_) => RecordKind::CommandExecuted,
+            RecordPayload::Panic { .. } => RecordKind::Panic,
+        }
+    }
+}
+
+/// A panic message, truncated to at most [`PANIC_MESSAGE_SIZE`] bytes so it fits in a record
+pub type PanicMessage = PanicText<PANIC_MESSAGE_SIZE>;
+
+/// The path of the file that panicked, truncated to at most [`PANIC_FILE_SIZE`] bytes with
+/// [`PanicText::from_path`]
+pub type PanicFile = PanicText<PANIC_FILE_SIZE>;
+
+/// Text from a panic, truncated to at most `N` bytes so it fits in a record.
+///
+/// This is built in the panic handler, so creating one never panics or allocates.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct PanicText<const N: usize> {
+    bytes: [u8; N],
+    len: u8,
+}
+
+impl<const N: usize> PanicText<N> {
+    /// Creates a text from `text`, truncating its end on a character boundary if it is too long
+    pub fn new(text: &str) -> Self {
+        let mut len = text.len().min(N);
+        while !text.is_char_boundary(len) {
+            len -= 1;
+        }
+        Self::from_bytes(&text.as_bytes()[..len])
+    }
+
+    /// Creates a text from the file `path`, truncating its start on a character boundary if it is
+    /// too long, as the file name at the end is the part that identifies it
+    pub fn from_path(path: &str) -> Self {
+        let mut start = path.len().saturating_sub(N);
+        while !path.is_char_boundary(start) {
+            start += 1;
+        }
+        Self::from_bytes(&path.as_bytes()[start..])
+    }
+
+    /// `text` must be valid UTF-8 and at most `N` bytes long
+    fn from_bytes(text: &[u8]) -> Self {
+        let mut bytes = [0; N];
+        bytes[..text.len()].copy_from_slice(text);
+        Self {
+            bytes,
+            len: text.len() as u8,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // `bytes` is always valid UTF-8 up to `len`, as it is checked when decoded
+        core::str::from_utf8(&self.bytes[..self.len as usize]).unwrap_or("")
+    }
+}
+
+impl<const N: usize> core::fmt::Debug for PanicText<N> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// A single timestamped entry in a flight log
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LogRecord {
+    /// Milliseconds since boot
+    pub timestamp_ms: u32,
+    pub payload: RecordPayload,
+}
+
+impl LogRecord {
+    pub fn new(timestamp_ms: u32, payload: RecordPayload) -> Self {
+        Self {
+            timestamp_ms,
+            payload,
+        }
+    }
+
+    /// Returns the number of bytes this record takes up once encoded
+    pub fn encoded_size(&self) -> usize {
+        HEADER_SIZE + self.payload.kind().payload_size()
+    }
+
+    /// Encodes this record into the start of `buf`, returning the number of bytes written
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, LogError> {
+        let len = self.encoded_size();
+        let buf = buf.get_mut(..len).ok_or(LogError::BufferTooSmall)?;
+        buf.fill(0);
+
+        buf[0] = self.payload.kind().to_byte();
+        buf[1..HEADER_SIZE].copy_from_slice(&self.timestamp_ms.to_le_bytes());
+
+        let payload = &mut buf[HEADER_SIZE..];
+        match self.payload {
+            RecordPayload::StateTransition { from, to, abort } => {
+                payload[0] = from;
+                payload[1] = to;
+                payload[2] = abort.map_or(0, |reason| abort_reason_to_byte(reason) + 1);
+            }
+            RecordPayload::SensorSample {
+                altitude,
+                velocity,
+                acceleration,
+                tilt,
+            } => {
+                for (i, value) in [altitude, velocity, acceleration, tilt].iter().enumerate() {
+                    payload[i * 4..i * 4 + 4].copy_from_slice(&value.to_le_bytes());
+                }
+            }
+            RecordPayload::CommandExecuted(object) => encode_command(object, payload),
+            RecordPayload::Panic {
+                file,
+                line,
+                message,
+            } => {
+                let file_start = 5 + PANIC_MESSAGE_SIZE;
+                payload[0..4].copy_from_slice(&line.to_le_bytes());
+                payload[4] = message.len;
+                payload[5..file_start].copy_from_slice(&message.bytes);
+                payload[file_start] = file.len;
+                payload[file_start + 1..].copy_from_slice(&file.bytes);
+            }
+        }
+
+        Ok(len)
+    }
+
+    /// Decodes the record at the start of `buf`, returning it and the number of bytes it took up
+    pub fn decode(buf: &[u8]) -> Result<(Self, usize), LogError> {
+        let kind = RecordKind::from_byte(*buf.first().ok_or(LogError::BufferTooSmall)?)?;
+        let len = HEADER_SIZE + kind.payload_size();
+        let buf = buf.get(..len).ok_or(LogError::BufferTooSmall)?;
+
+        let timestamp_ms = u32::from_le_bytes([buf[1], buf[2], buf[3], buf[4]]);
+        let payload = &buf[HEADER_SIZE..];
+        let payload = match kind {
+            RecordKind::StateTransition => RecordPayload::StateTransition {
+                from: payload[0],
+                to: payload[1],
+                abort: match payload[2] {
+                    0 => None,
+                    byte => Some(abort_reason_from_byte(byte - 1)?),
+                },
+            },
+            RecordKind::SensorSample => RecordPayload::SensorSample {
+                altitude: read_f32(&payload[0..4]),
+                velocity: read_f32(&payload[4..8]),
+                acceleration: read_f32(&payload[8..12]),
+                tilt: read_f32(&payload[12..16]),
+            },
+            RecordKind::CommandExecuted => RecordPayload::CommandExecuted(decode_command(payload)?),
+            RecordKind::Panic => {
+                let (message, file) = payload[4..].split_at(1 + PANIC_MESSAGE_SIZE);
+                RecordPayload::Panic {
+                    file: PanicFile::new(decode_text(file)?),
+                    line: u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]),
+                    message: PanicMessage::new(decode_text(message)?),
+                }
+            }
+        };
+
+        Ok((Self::new(timestamp_ms, payload), len))
+    }
+}
+
+/// Formats the record as a single line, such as `1.500 state 1 -> 2`
+impl core::fmt::Display for LogRecord {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{}.{:03} ",
+            self.timestamp_ms / 1000,
+            self.timestamp_ms % 1000
+        )?;
+        match self.payload {
+            RecordPayload::StateTransition { from, to, abort } => {
+                write!(f, "state {} -> {}", from, to)?;
+                if let Some(reason) = abort {
+                    write!(f, " abort {:?}", reason)?;
+                }
+                Ok(())
+            }
+            RecordPayload::SensorSample {
+                altitude,
+                velocity,
+                acceleration,
+                tilt,
+            } => write!(
+                f,
+                "sample alt={:.1} vel={:.1} acc={:.1} tilt={:.1}",
+                altitude, velocity, acceleration, tilt
+            ),
+            RecordPayload::CommandExecuted(object) => write!(f, "command {}", object),
+            RecordPayload::Panic {
+                file,
+                line,
+                message,
+            } => write!(f, "panic {}:{}: {}", file.as_str(), line, message.as_str()),
+        }
+    }
+}
+
+/// The byte erased flash reads back as, which marks the end of a log
+pub const ERASED: u8 = 0xFF;
+
+/// Iterates over the records encoded back to back in `buf`, such as pages downloaded from the
+/// flash log
+pub fn records(buf: &[u8]) -> Records<'_> {
+    Records {
+        buf,
+        offset: 0,
+        done: false,
+    }
+}
+
+/// An iterator over the records in a log, created by [`records`].
+///
+/// This ends at the end of the buffer or at erased flash. A record that does not decode, such as
+/// one cut short by a power loss, is returned as an error and ends the iteration, as the start of
+/// the next record can no longer be found.
+#[derive(Debug, Clone)]
+pub struct Records<'a> {
+    buf: &'a [u8],
+    offset: usize,
+    done: bool,
+}
+
+impl<'a> Records<'a> {
+    /// Returns the offset in the buffer of the next record to be decoded
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Result<LogRecord, LogError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.buf[self.offset..];
+        if self.done || rest.first().is_none_or(|byte| *byte == ERASED) {
+            return None;
+        }
+
+        match LogRecord::decode(rest) {
+            Ok((record, len)) => {
+                self.offset += len;
+                Some(Ok(record))
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// A destination for log records, such as the flash logger in flight or an in-memory recorder in
+/// tests.
+///
+/// Failing to log must never stop a flight, so callers should report errors from a sink rather
+/// than propagate them.
+pub trait LogSink {
+    type Error;
+
+    /// Writes `record` to the end of the log
+    fn write(&mut self, record: &LogRecord) -> Result<(), Self::Error>;
+}
+
+/// Records are kept in memory until the vector is full, after which writes fail
+impl<const N: usize> LogSink for heapless::Vec<LogRecord, N> {
+    type Error = LogError;
+
+    fn write(&mut self, record: &LogRecord) -> Result<(), Self::Error> {
+        self.push(*record).map_err(|_| LogError::BufferTooSmall)
+    }
+}
+
+/// Decodes a panic text from its length byte followed by its bytes
+fn decode_text(encoded: &[u8]) -> Result<&str, LogError> {
+    let len = encoded[0] as usize;
+    let bytes = encoded[1..].get(..len).ok_or(LogError::InvalidPayload)?;
+    core::str::from_utf8(bytes).map_err(|_| LogError::InvalidPayload)
+}
+
+fn read_f32(bytes: &[u8]) -> f32 {
+    f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn abort_reason_to_byte(reason: AbortReason) -> u8 {
+    match reason {
+        AbortReason::SensorFault => 0,
+        AbortReason::Timeout => 1,
+        AbortReason::ContinuityLoss => 2,
+        AbortReason::TiltExceeded => 3,
+        AbortReason::Manual => 4,
+        AbortReason::LowBattery => 5,
+    }
+}
+
+fn abort_reason_from_byte(byte: u8) -> Result<AbortReason, LogError> {
+    match byte {
+        0 => Ok(AbortReason::SensorFault),
+        1 => Ok(AbortReason::Timeout),
+        2 => Ok(AbortReason::ContinuityLoss),
+        3 => Ok(AbortReason::TiltExceeded),
+        4 => Ok(AbortReason::Manual),
+        5 => Ok(AbortReason::LowBattery),
+        _ => Err(LogError::InvalidPayload),
+    }
+}
+
+/// Encodes `object` as a tag byte followed by its value, into a zeroed 6 byte `payload`
+fn encode_command(object: CommandObject, payload: &mut [u8]) {
+    match object {
+        CommandObject::Pyro1(value) => {
+            payload[0] = 0;
+            payload[1] = value as u8;
+        }
+        CommandObject::Pyro2(value) => {
+            payload[0] = 1;
+            payload[1] = value as u8;
+        }
+        CommandObject::Pyro3(value) => {
+            payload[0] = 2;
+            payload[1] = value as u8;
+        }
+        CommandObject::Beacon(value) => {
+            payload[0] = 3;
+            payload[1] = value as u8;
+        }
+        CommandObject::DataRate(rate) => {
+            payload[0] = 4;
+            payload[1..3].copy_from_slice(&rate.to_le_bytes());
+        }
+        CommandObject::Servo { channel, angle_deg } => {
+            payload[0] = 5;
+            payload[1] = channel;
+            payload[2..6].copy_from_slice(&angle_deg.to_le_bytes());
+        }
+    }
+}
+
+fn decode_command(payload: &[u8]) -> Result<CommandObject, LogError> {
+    let flag = || match payload[1] {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(LogError::InvalidPayload),
+    };
+
+    Ok(match payload[0] {
+        0 => CommandObject::Pyro1(flag()?),
+        1 => CommandObject::Pyro2(flag()?),
+        2 => CommandObject::Pyro3(flag()?),
+        3 => CommandObject::Beacon(flag()?),
+        4 => CommandObject::DataRate(u16::from_le_bytes([payload[1], payload[2]])),
+        5 => CommandObject::Servo {
+            channel: payload[1],
+            angle_deg: read_f32(&payload[2..6]),
+        },
+        _ => return Err(LogError::InvalidPayload),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        records, LogError, LogRecord, LogSink, PanicFile, PanicMessage, RecordPayload,
+        MAX_RECORD_SIZE, PANIC_FILE_SIZE, PANIC_MESSAGE_SIZE,
+    };
+    use crate::{AbortReason, CommandObject};
+    use core::fmt::Write;
+    use heapless::Vec;
+
+    fn round_trip(record: LogRecord) {
+        let mut buf = [0; MAX_RECORD_SIZE];
+        let len = record.encode(&mut buf).unwrap();
+        assert_eq!(len, record.encoded_size());
+        assert_eq!(LogRecord::decode(&buf[..len]), Ok((record, len)));
+    }
+
+    #[test]
+    fn test_state_transition() {
+        round_trip(LogRecord::new(
+            1_500,
+            RecordPayload::StateTransition {
+                from: 1,
+                to: 2,
+                abort: None,
+            },
+        ));
+        round_trip(LogRecord::new(
+            u32::MAX,
+            RecordPayload::StateTransition {
+                from: 3,
+                to: 0,
+                abort: Some(AbortReason::Manual),
+            },
+        ));
+        round_trip(LogRecord::new(
+            0,
+            RecordPayload::StateTransition {
+                from: 0,
+                to: 4,
+                abort: Some(AbortReason::LowBattery),
+            },
+        ));
+    }
+
+    #[test]
+    fn test_sensor_sample() {
+        round_trip(LogRecord::new(
+            42,
+            RecordPayload::SensorSample {
+                altitude: 1234.5,
+                velocity: -12.25,
+                acceleration: 9.81,
+                tilt: 3.0,
+            },
+        ));
+    }
+
+    #[test]
+    fn test_command_executed() {
+        let objects = [
+            CommandObject::Pyro1(true),
+            CommandObject::Pyro2(false),
+            CommandObject::Pyro3(true),
+            CommandObject::Beacon(true),
+            CommandObject::DataRate(500),
+            CommandObject::Servo {
+                channel: 1,
+                angle_deg: -30.0,
+            },
+        ];
+        for object in objects {
+            round_trip(LogRecord::new(7, RecordPayload::CommandExecuted(object)));
+        }
+    }
+
+    #[test]
+    fn test_panic() {
+        let record = LogRecord::new(
+            9_000,
+            RecordPayload::Panic {
+                file: PanicFile::from_path("src/flight/state_machine.rs"),
+                line: 212,
+                message: PanicMessage::new("index out of bounds"),
+            },
+        );
+        round_trip(record);
+        let mut line = heapless::String::<64>::new();
+        write!(line, "{}", record).unwrap();
+        assert_eq!(
+            line,
+            "9.000 panic state_machine.rs:212: index out of bounds"
+        );
+
+        // Paths keep their end, which names the file, without splitting a character either
+        let file = PanicFile::from_path("src/μ/driver/fire.rs");
+        assert_eq!(file.as_str(), "/driver/fire.rs");
+        assert!(file.as_str().len() <= PANIC_FILE_SIZE);
+        assert_eq!(PanicFile::from_path("main.rs").as_str(), "main.rs");
+
+        // Truncated to fit, without splitting the multi-byte character at the boundary
+        let message = PanicMessage::new("altitude overflowed at 10°C or so");
+        assert_eq!(message.as_str(), "altitude overflowed at 1");
+        let message = PanicMessage::new("pressure sensor hung xy°C");
+        assert_eq!(message.as_str(), "pressure sensor hung xy");
+        assert!(message.as_str().len() <= PANIC_MESSAGE_SIZE);
+
+        round_trip(LogRecord::new(
+            0,
+            RecordPayload::Panic {
+                file,
+                line: 1,
+                message,
+            },
+        ));
+    }
+
+    #[test]
+    fn test_consecutive_records() {
+        let first = LogRecord::new(
+            10,
+            RecordPayload::CommandExecuted(CommandObject::Beacon(true)),
+        );
+        let second = LogRecord::new(
+            20,
+            RecordPayload::StateTransition {
+                from: 0,
+                to: 1,
+                abort: None,
+            },
+        );
+
+        let mut buf = [0; 2 * MAX_RECORD_SIZE];
+        let len = first.encode(&mut buf).unwrap();
+        second.encode(&mut buf[len..]).unwrap();
+
+        let (decoded, used) = LogRecord::decode(&buf).unwrap();
+        assert_eq!(decoded, first);
+        assert_eq!(LogRecord::decode(&buf[used..]).unwrap().0, second);
+    }
+
+    #[test]
+    fn test_truncated_buffer() {
+        let record = LogRecord::new(
+            100,
+            RecordPayload::SensorSample {
+                altitude: 0.0,
+                velocity: 0.0,
+                acceleration: 0.0,
+                tilt: 0.0,
+            },
+        );
+        let mut buf = [0; MAX_RECORD_SIZE];
+        let len = record.encode(&mut buf).unwrap();
+
+        assert_eq!(
+            LogRecord::decode(&buf[..len - 1]),
+            Err(LogError::BufferTooSmall)
+        );
+        assert_eq!(LogRecord::decode(&[]), Err(LogError::BufferTooSmall));
+        assert_eq!(
+            record.encode(&mut buf[..len - 1]),
+            Err(LogError::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_unknown_kind() {
+        // Erased flash reads back as all ones
+        let buf = [0xFF; MAX_RECORD_SIZE];
+        assert_eq!(LogRecord::decode(&buf), Err(LogError::UnknownKind(0xFF)));
+    }
+
+    #[test]
+    fn test_vec_sink() {
+        let mut sink: Vec<LogRecord, 2> = Vec::new();
+        let record = |to| {
+            LogRecord::new(
+                0,
+                RecordPayload::StateTransition {
+                    from: 0,
+                    to,
+                    abort: None,
+                },
+            )
+        };
+
+        assert_eq!(sink.write(&record(1)), Ok(()));
+        assert_eq!(sink.write(&record(2)), Ok(()));
+        assert_eq!(sink.write(&record(3)), Err(LogError::BufferTooSmall));
+        assert_eq!(sink, [record(1), record(2)]);
+    }
+
+    #[test]
+    fn test_records() {
+        let first = LogRecord::new(
+            1_500,
+            RecordPayload::StateTransition {
+                from: 1,
+                to: 2,
+                abort: Some(AbortReason::TiltExceeded),
+            },
+        );
+        let second = LogRecord::new(
+            1_510,
+            RecordPayload::CommandExecuted(CommandObject::DataRate(50)),
+        );
+
+        // Followed by erased flash
+        let mut buf = [0xFF; 3 * MAX_RECORD_SIZE];
+        let len = first.encode(&mut buf).unwrap();
+        let len = len + second.encode(&mut buf[len..]).unwrap();
+        let mut iter = records(&buf);
+        assert_eq!(iter.next(), Some(Ok(first)));
+        assert_eq!(iter.next(), Some(Ok(second)));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.offset(), len);
+
+        // A record cut short ends the log with an error
+        let mut iter = records(&buf[..len - 1]);
+        assert_eq!(iter.next(), Some(Ok(first)));
+        assert_eq!(iter.next(), Some(Err(LogError::BufferTooSmall)));
+        assert_eq!(iter.next(), None);
+
+        let mut line = heapless::String::<64>::new();
+        core::fmt::Write::write_fmt(&mut line, format_args!("{}", first)).unwrap();
+        assert_eq!(line, "1.500 state 1 -> 2 abort TiltExceeded");
+    }
+}
@@ -0,0 +1,293 @@
+//! Test doubles for the interfaces the state machine runs against, so that reference states can be
+//! driven in tests without any hardware.
+
+use crate::clock::Clock;
+use crate::controls::Controls;
+use crate::workspace::Workspace;
+use crate::{CheckKind, CommandObject, ObjectState, Seconds};
+
+use std::vec::Vec;
+
+/// A [`Clock`] whose time is set explicitly by a test, and only moves when the test advances it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MockClock {
+    now: Seconds,
+}
+
+impl MockClock {
+    /// Creates a clock reading `now`
+    pub fn new(now: Seconds) -> Self {
+        Self { now }
+    }
+
+    /// Sets the time to `now`
+    pub fn set(&mut self, now: Seconds) {
+        self.now = now;
+    }
+
+    /// Moves the time forward by `duration`
+    pub fn advance(&mut self, duration: Seconds) {
+        self.now = Seconds(self.now.0 + duration.0);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Seconds {
+        self.now
+    }
+}
+
+/// A [`Workspace`] which returns whatever value a test has set for each kind, including values of
+/// the wrong variant or invalid readings, so that fault handling can be tested too
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MockDataWorkspace {
+    values: Vec<(CheckKind, ObjectState)>,
+}
+
+impl MockDataWorkspace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `kind` return `value`, or no value if `value` is `None`
+    pub fn set(&mut self, kind: CheckKind, value: Option<ObjectState>) {
+        self.values.retain(|(set, _)| *set != kind);
+        if let Some(value) = value {
+            self.values.push((kind, value));
+        }
+    }
+}
+
+impl Workspace for MockDataWorkspace {
+    fn get_object(&self, kind: CheckKind) -> Option<ObjectState> {
+        self.values
+            .iter()
+            .find(|(set, _)| *set == kind)
+            .map(|(_, value)| *value)
+    }
+}
+
+/// [`Controls`] which record every object they are set to, along with the time it was set at
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockControls {
+    /// The time recorded with each call to [`Controls::set`]
+    pub now: Seconds,
+    calls: Vec<(Seconds, CommandObject)>,
+}
+
+impl MockControls {
+    pub fn new() -> Self {
+        Self {
+            now: Seconds(0.0),
+            calls: Vec::new(),
+        }
+    }
+
+    /// Returns every object set so far, in order, with the time it was set at
+    pub fn calls(&self) -> &[(Seconds, CommandObject)] {
+        &self.calls
+    }
+}
+
+impl Default for MockControls {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Controls for MockControls {
+    fn set(&mut self, object: CommandObject) {
+        self.calls.push((self.now, object));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MockClock, MockControls, MockDataWorkspace};
+    use crate::builder::{ConfigBuilder, StateBuilder, Transition};
+    use crate::clock::Clock;
+    use crate::controls::Controls;
+    use crate::reference::{CommandAction, State, StateTransition};
+    use crate::workspace::Workspace;
+    use crate::{
+        indices_to_refs, CheckData, CheckKind, CommandObject, FloatCondition, NativeFlagCondition,
+        ObjectState, Seconds,
+    };
+
+    use static_alloc::Bump;
+
+    /// Runs one execution of `state`, which was entered `elapsed` seconds ago, the way the flight
+    /// computer does, and returns the state to transition to, if any
+    fn execute<'s>(
+        state: &State<'s>,
+        elapsed: f32,
+        workspace: &impl Workspace,
+        controls: &mut impl Controls,
+    ) -> Option<&'s State<'s>> {
+        for command in state.commands.iter() {
+            if let CommandAction::Execute = state.poll_command(command, elapsed, |_| true) {
+                controls.set(command.object);
+            }
+        }
+        let snapshot = state.snapshot(|kind| workspace.get_object(kind));
+        state
+            .evaluate_checks(|data| snapshot.satisfies(data))
+            .map(|(_, transition)| match transition {
+                StateTransition::Transition(next) | StateTransition::Abort(next, _) => next,
+            })
+    }
+
+    #[test]
+    fn test_mocks_drive_transition() {
+        static A: Bump<[u8; 1 << 12]> = Bump::uninit();
+
+        let config = ConfigBuilder::new(0)
+            .state(
+                StateBuilder::new(0)
+                    .command(CommandObject::Beacon(true), Seconds(0.0))
+                    .check(
+                        CheckData::ApogeeFlag(NativeFlagCondition(true)),
+                        Some(Transition::To(1)),
+                    ),
+            )
+            .state(
+                StateBuilder::new(1)
+                    .pulse(CommandObject::Pyro1(true), Seconds(0.0), Seconds(1.0))
+                    .check(
+                        CheckData::Altitude(FloatCondition::LessThan(300.0)),
+                        Some(Transition::To(2)),
+                    ),
+            )
+            .state(StateBuilder::new(2).terminal())
+            .build()
+            .unwrap();
+        let states = indices_to_refs(&config, &A).unwrap();
+
+        let mut workspace = MockDataWorkspace::new();
+        workspace.set(CheckKind::ApogeeFlag, Some(ObjectState::Flag(false)));
+        workspace.set(CheckKind::Altitude, Some(ObjectState::Float(1200.0)));
+        let mut controls = MockControls::new();
+
+        // Coasting, apogee hasn't been detected yet
+        let coast = &states[0];
+        coast.reset_commands();
+        assert!(execute(coast, 0.0, &workspace, &mut controls).is_none());
+        controls.now = Seconds(5.0);
+        assert!(execute(coast, 5.0, &workspace, &mut controls).is_none());
+
+        // Apogee moves to the drogue state, which fires on entry
+        controls.now = Seconds(8.2);
+        workspace.set(CheckKind::ApogeeFlag, Some(ObjectState::Flag(true)));
+        let drogue = execute(coast, 8.2, &workspace, &mut controls).unwrap();
+        assert_eq!(drogue.id, 1);
+        drogue.reset_commands();
+        assert!(execute(drogue, 0.0, &workspace, &mut controls).is_none());
+
+        // An altitude with no value never satisfies the main check
+        workspace.set(CheckKind::Altitude, None);
+        controls.now = Seconds(9.0);
+        assert!(execute(drogue, 0.8, &workspace, &mut controls).is_none());
+
+        assert_eq!(
+            controls.calls(),
+            &[
+                (Seconds(0.0), CommandObject::Beacon(true)),
+                (Seconds(8.2), CommandObject::Pyro1(true)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mock_clock_command_delay() {
+        static A: Bump<[u8; 1 << 12]> = Bump::uninit();
+
+        let config = ConfigBuilder::new(0)
+            .state(
+                StateBuilder::new(0)
+                    .command(CommandObject::Pyro2(true), Seconds(1.5))
+                    .terminal(),
+            )
+            .build()
+            .unwrap();
+        let states = indices_to_refs(&config, &A).unwrap();
+        let workspace = MockDataWorkspace::new();
+        let mut controls = MockControls::new();
+
+        let mut clock = MockClock::new(Seconds(20.0));
+        let entered = clock.now();
+        states[0].reset_commands();
+
+        // Time only moves when the test advances it, however often the state executes
+        for _ in 0..3 {
+            execute(
+                &states[0],
+                clock.elapsed_since(entered),
+                &workspace,
+                &mut controls,
+            );
+        }
+        clock.advance(Seconds(1.0));
+        execute(
+            &states[0],
+            clock.elapsed_since(entered),
+            &workspace,
+            &mut controls,
+        );
+        assert!(controls.calls().is_empty());
+
+        // Advancing past the delay fires the command
+        clock.advance(Seconds(0.75));
+        controls.now = clock.now();
+        execute(
+            &states[0],
+            clock.elapsed_since(entered),
+            &workspace,
+            &mut controls,
+        );
+        assert_eq!(
+            controls.calls(),
+            &[(Seconds(21.75), CommandObject::Pyro2(true))]
+        );
+
+        clock.set(Seconds(30.0));
+        assert_eq!(clock.elapsed_since(entered), 10.0);
+    }
+
+    #[test]
+    fn test_disarm_all() {
+        let mut controls = MockControls::new();
+        controls.set(CommandObject::Pyro1(true));
+        controls.set(CommandObject::Pyro3(true));
+        controls.set(CommandObject::Beacon(true));
+
+        // Disarming twice is the same as disarming once
+        controls.disarm_all();
+        controls.disarm_all();
+
+        let last = |pin: fn(bool) -> CommandObject| {
+            controls
+                .calls()
+                .iter()
+                .rev()
+                .find(|(_, object)| *object == pin(true) || *object == pin(false))
+                .map(|(_, object)| *object)
+        };
+        assert_eq!(
+            last(CommandObject::Pyro1),
+            Some(CommandObject::Pyro1(false))
+        );
+        assert_eq!(
+            last(CommandObject::Pyro2),
+            Some(CommandObject::Pyro2(false))
+        );
+        assert_eq!(
+            last(CommandObject::Pyro3),
+            Some(CommandObject::Pyro3(false))
+        );
+        // Only the pyro channels are touched
+        assert_eq!(
+            last(CommandObject::Beacon),
+            Some(CommandObject::Beacon(true))
+        );
+    }
+}
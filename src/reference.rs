@@ -3,7 +3,7 @@
 //! reference a different state is important
 
 use core::cell::Cell;
-use core::sync::atomic::AtomicBool;
+use core::sync::atomic::{AtomicBool, Ordering};
 use heapless::Vec;
 
 use crate::{frozen::FrozenVec, MAX_CHECKS_PER_STATE, MAX_COMMANDS_PER_STATE, MAX_STATES};
@@ -13,35 +13,65 @@ pub struct ConfigFile<'s> {
     pub states: Vec<&'s State<'s>, MAX_STATES>,
 }
 
+#[derive(Copy, Clone)]
 pub struct Timeout<'s> {
     pub time: f32,
     pub transition: StateTransition<'s>,
 }
 
 impl<'s> Timeout<'s> {
-    pub fn new(time: f32, transition: StateTransition<'s>) -> Self {
+    pub const fn new(time: f32, transition: StateTransition<'s>) -> Self {
         Self { time, transition }
     }
 }
 
+/// How long a pyro command may wait for continuity once it is due, and the abort made once it
+/// has waited that long. This is the reference form of
+/// [`index::ContinuityGuard`](crate::index::ContinuityGuard)
+#[derive(Copy, Clone)]
+pub struct ContinuityGuard<'s> {
+    /// Time in seconds a due pyro command may wait
+    pub timeout: f32,
+    pub abort: StateTransition<'s>,
+}
+
+impl<'s> ContinuityGuard<'s> {
+    pub const fn new(timeout: f32, abort: StateTransition<'s>) -> Self {
+        Self { timeout, abort }
+    }
+}
+
 pub struct State<'s> {
     pub id: u8,
+    /// A name which is shown in logs alongside `id`, such as `Drogue`
+    pub name: Option<crate::StateName>,
     pub checks: FrozenVec<&'s Check<'s>, MAX_CHECKS_PER_STATE>,
     pub commands: FrozenVec<&'s Command, MAX_COMMANDS_PER_STATE>,
     pub timeout: Cell<Option<Timeout<'s>>>,
+    /// The transition that is made when a check in this state reads an invalid sensor value
+    pub fault_transition: Cell<Option<StateTransition<'s>>>,
+    /// The minimum time this state must be active for before any check or timeout can transition
+    pub min_dwell: crate::Seconds,
+    /// Holds off pyro commands until their channel has continuity. `None` fires pyro commands
+    /// without checking continuity
+    pub continuity_guard: Cell<Option<ContinuityGuard<'s>>>,
 }
 
 impl<'s> State<'s> {
     pub(crate) fn new(id: u8) -> Self {
         Self {
             id,
+            name: None,
             checks: FrozenVec::new(),
             commands: FrozenVec::new(),
             timeout: Cell::new(None),
+            fault_transition: Cell::new(None),
+            min_dwell: crate::Seconds(0.0),
+            continuity_guard: Cell::new(None),
         }
     }
 
-    pub fn new_complete(
+    pub const fn new_complete(
         id: u8,
         checks: FrozenVec<&'s Check<'s>, MAX_CHECKS_PER_STATE>,
         commands: FrozenVec<&'s Command, MAX_COMMANDS_PER_STATE>,
@@ -49,28 +79,239 @@ impl<'s> State<'s> {
     ) -> Self {
         Self {
             id,
+            name: None,
             checks,
             commands,
             timeout: Cell::new(timeout),
+            fault_transition: Cell::new(None),
+            min_dwell: crate::Seconds(0.0),
+            continuity_guard: Cell::new(None),
+        }
+    }
+
+    /// Returns a label for this state to use in logs, such as in the message printed on each
+    /// transition
+    pub fn label(&self) -> crate::StateLabel<'_> {
+        crate::StateLabel {
+            id: self.id,
+            name: self.name.as_deref(),
         }
     }
+
+    /// Clears `was_executed` on every command that repeats on re-entry, so that they are executed
+    /// again, and restarts the period of every periodic command. This should be called each time
+    /// this state is entered
+    pub fn reset_commands(&self) {
+        for command in self.commands.iter() {
+            if command.repeat != crate::Repeat::Never {
+                command.was_executed.store(false, Ordering::Relaxed);
+                command.last_executed.set(None);
+            }
+        }
+    }
+
+    /// Decides what to do with `command`, one of this state's commands, given the time in seconds
+    /// since this state was entered.
+    ///
+    /// `has_continuity` is only called for a due pyro command while [`Self::continuity_guard`] is
+    /// set. A command left waiting for continuity is retried on the next call, until the guard's
+    /// timeout has passed since it was due
+    pub fn poll_command<F>(
+        &self,
+        command: &Command,
+        elapsed_in_state: f32,
+        has_continuity: F,
+    ) -> CommandAction<'s>
+    where
+        F: FnOnce(crate::CheckKind) -> bool,
+    {
+        if !command.is_due(elapsed_in_state) {
+            return CommandAction::Wait;
+        }
+        if let (Some(guard), Some(kind)) = (
+            self.continuity_guard.get(),
+            command.object.continuity_check(),
+        ) {
+            if !has_continuity(kind) {
+                return if elapsed_in_state - command.due_at() >= guard.timeout {
+                    CommandAction::Abort(guard.abort)
+                } else {
+                    CommandAction::Wait
+                };
+            }
+        }
+        command.mark_executed(elapsed_in_state);
+        CommandAction::Execute
+    }
+
+    /// Returns true once this state has been active for at least `min_dwell`, given the time in
+    /// seconds since it was entered. No transition should be taken before then
+    pub fn dwell_elapsed(&self, elapsed_in_state: f32) -> bool {
+        elapsed_in_state >= self.min_dwell.0
+    }
+
+    /// Clears the debounce count of every check. This should be called each time this state is
+    /// entered
+    pub fn reset_checks(&self) {
+        for check in self.checks.iter() {
+            check.satisfied_count.set(0);
+        }
+    }
+
+    /// Runs every check in this state and returns the transition that should be taken, if any,
+    /// along with the index of the check it belongs to as listed in the config.
+    ///
+    /// `is_satisfied` is called once per check to evaluate its condition against the current data.
+    /// Ungrouped checks transition on their own, while checks that share a `group` only transition
+    /// once all of them are satisfied, taking the first transition in the group.
+    pub fn evaluate_checks<F>(&self, mut is_satisfied: F) -> Option<(usize, StateTransition<'s>)>
+    where
+        F: FnMut(&crate::CheckData) -> bool,
+    {
+        // Every check must be updated each execution to keep their debounce counts accurate
+        let mut satisfied = [false; MAX_CHECKS_PER_STATE];
+        for (i, check) in self.checks.iter().enumerate() {
+            satisfied[i] = check.update(is_satisfied(&check.data));
+        }
+
+        for (i, check) in self.checks.iter().enumerate() {
+            let transition = match check.transition {
+                Some(transition) => transition,
+                None => continue,
+            };
+
+            let fires = match check.group {
+                None => satisfied[i],
+                Some(group) => self
+                    .checks
+                    .iter()
+                    .zip(satisfied.iter())
+                    .filter(|(other, _)| other.group == Some(group))
+                    .all(|(_, satisfied)| *satisfied),
+            };
+
+            if fires {
+                return Some((usize::from(check.index), transition));
+            }
+        }
+
+        None
+    }
+
+    /// Reads the current value of each kind of data this state's checks use, calling `fetch` once
+    /// per kind. Evaluating every check against the snapshot means several checks on the same
+    /// value only read it once per execution
+    pub fn snapshot<F>(&self, mut fetch: F) -> Snapshot
+    where
+        F: FnMut(crate::CheckKind) -> Option<crate::ObjectState>,
+    {
+        let mut snapshot = Snapshot { values: Vec::new() };
+        for check in self.checks.iter() {
+            let kind = check.data.kind();
+            if snapshot.values.iter().all(|(read, _)| *read != kind) {
+                // There is at most one kind per check, so this cannot overflow
+                let _ = snapshot.values.push((kind, fetch(kind)));
+            }
+        }
+        snapshot
+    }
+}
+
+/// The values of the data a state's checks read during one execution, taken by
+/// [`State::snapshot`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    values: Vec<(crate::CheckKind, Option<crate::ObjectState>), MAX_CHECKS_PER_STATE>,
+}
+
+impl Snapshot {
+    /// Returns the value of `kind`, or `None` if it was not read or had no value
+    pub fn get(&self, kind: crate::CheckKind) -> Option<crate::ObjectState> {
+        self.values
+            .iter()
+            .find(|(read, _)| *read == kind)
+            .and_then(|(_, value)| *value)
+    }
+
+    /// Returns true if `data` is satisfied by the value read for its kind
+    pub fn satisfies(&self, data: &crate::CheckData) -> bool {
+        self.get(data.kind())
+            .is_some_and(|value| data.is_satisfied(value))
+    }
+
+    /// Returns true if any value read is invalid, such as a NaN from a failed sensor
+    pub fn has_invalid(&self) -> bool {
+        self.values
+            .iter()
+            .any(|(_, value)| value.is_some_and(|value| !value.is_valid()))
+    }
 }
 
 pub struct Check<'s> {
     pub data: crate::CheckData,
     pub transition: Option<StateTransition<'s>>,
+    /// The number of consecutive executions this check must be satisfied for before it transitions
+    pub debounce: u8,
+    /// Checks in the same state that share a group must all be satisfied before any of them
+    /// transition
+    pub group: Option<u8>,
+    /// The priority this check was ordered by when the state was converted. A state's checks are
+    /// stored highest priority first
+    pub priority: u8,
+    /// The position of this check in its state as listed in the config, which
+    /// [`State::evaluate_checks`] reports. This differs from its position in [`State::checks`]
+    /// when the checks were reordered by priority
+    pub index: u8,
+    /// The number of consecutive executions this check has been satisfied for
+    pub satisfied_count: Cell<u8>,
 }
 
 impl<'s> Check<'s> {
-    pub fn new(data: crate::CheckData, transition: Option<StateTransition<'s>>) -> Self {
-        Self { data, transition }
+    pub const fn new(
+        data: crate::CheckData,
+        transition: Option<StateTransition<'s>>,
+        index: u8,
+    ) -> Self {
+        Self {
+            data,
+            transition,
+            debounce: 1,
+            group: None,
+            priority: 0,
+            index,
+            satisfied_count: Cell::new(0),
+        }
+    }
+
+    /// Records whether this check's condition was `satisfied` during this execution, and returns
+    /// true once it has been satisfied for `debounce` consecutive executions
+    pub fn update(&self, satisfied: bool) -> bool {
+        if !satisfied {
+            self.satisfied_count.set(0);
+            return false;
+        }
+
+        let count = self.satisfied_count.get().saturating_add(1);
+        self.satisfied_count.set(count);
+        count >= self.debounce
     }
 }
 
+/// What to do with a command, as decided by [`State::poll_command`]
+#[derive(Copy, Clone)]
+pub enum CommandAction<'s> {
+    /// The command is not due yet, or is waiting for continuity
+    Wait,
+    /// The command should be executed now
+    Execute,
+    /// The command waited too long for continuity, so this abort should be made instead
+    Abort(StateTransition<'s>),
+}
+
 #[derive(Copy, Clone)]
 pub enum StateTransition<'s> {
     Transition(&'s State<'s>),
-    Abort(&'s State<'s>),
+    Abort(&'s State<'s>, crate::AbortReason),
 }
 
 /// An action that takes place at a specific time after the state containing this is entered
@@ -82,16 +323,492 @@ pub struct Command {
     /// How long after the state activates to execute this command
     pub delay: crate::Seconds,
 
+    /// Whether this command executes again after it first executes. For
+    /// [`Repeat::Periodic`](crate::Repeat::Periodic) commands, `delay` is the interval between
+    /// executions
+    pub repeat: crate::Repeat,
+
+    /// For pyro commands, how long the channel stays on before automatically turning off. `None`
+    /// leaves it on until another command turns it off
+    pub pulse: Option<crate::Seconds>,
+
     /// If this command has already executed
     pub was_executed: AtomicBool,
+
+    /// The time in seconds since the state was entered when this command last executed
+    pub last_executed: Cell<Option<f32>>,
 }
 
 impl Command {
-    pub fn new(object: crate::CommandObject, delay: crate::Seconds) -> Self {
+    pub const fn new(object: crate::CommandObject, delay: crate::Seconds) -> Self {
         Self {
             object,
             delay,
+            repeat: crate::Repeat::Never,
+            pulse: None,
             was_executed: AtomicBool::new(false),
+            last_executed: Cell::new(None),
+        }
+    }
+
+    /// Returns true if this command should execute now, given the time in seconds since its state
+    /// was entered, and records that it has executed.
+    ///
+    /// A periodic command is due every `delay` after it last executed, and first `delay` after
+    /// the state is entered. Any other command is due once, `delay` after the state is entered
+    pub fn poll(&self, elapsed_in_state: f32) -> bool {
+        if !self.is_due(elapsed_in_state) {
+            return false;
+        }
+        self.mark_executed(elapsed_in_state);
+        true
+    }
+
+    /// Returns true if this command should execute now, without recording that it has. See
+    /// [`Self::poll`]
+    pub fn is_due(&self, elapsed_in_state: f32) -> bool {
+        let already_executed =
+            self.repeat != crate::Repeat::Periodic && self.was_executed.load(Ordering::Relaxed);
+        !already_executed && elapsed_in_state >= self.due_at()
+    }
+
+    /// Returns the time in seconds since its state was entered at which this command is next due
+    pub fn due_at(&self) -> f32 {
+        match (self.repeat, self.last_executed.get()) {
+            (crate::Repeat::Periodic, Some(last)) => last + self.delay.0,
+            _ => self.delay.0,
+        }
+    }
+
+    /// Records that this command executed at `elapsed_in_state`
+    pub fn mark_executed(&self, elapsed_in_state: f32) {
+        self.was_executed.store(true, Ordering::Relaxed);
+        self.last_executed.set(Some(elapsed_in_state));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Check, Command, CommandAction, State, StateTransition};
+    use crate::{
+        frozen::FrozenVec, AbortReason, CheckData, CheckKind, CommandObject, FloatCondition,
+        NativeFlagCondition, ObjectState, PyroContinuityCondition, Repeat, Seconds,
+    };
+    use core::sync::atomic::Ordering;
+
+    #[test]
+    fn test_debounce() {
+        let mut check = Check::new(
+            CheckData::Altitude(FloatCondition::LessThan(300.0)),
+            None,
+            0,
+        );
+        check.debounce = 3;
+
+        assert!(!check.update(true));
+        assert!(!check.update(true));
+        // A single noisy sample resets the count
+        assert!(!check.update(false));
+        assert!(!check.update(true));
+        assert!(!check.update(true));
+        assert!(check.update(true));
+    }
+
+    #[test]
+    fn test_reset_checks() {
+        let mut check = Check::new(
+            CheckData::Altitude(FloatCondition::LessThan(300.0)),
+            None,
+            0,
+        );
+        check.debounce = 2;
+
+        let checks = FrozenVec::new();
+        assert!(checks.push(&check).is_ok());
+        let state = State::new_complete(0, checks, FrozenVec::new(), None);
+
+        assert!(!check.update(true));
+        state.reset_checks();
+        assert!(!check.update(true));
+        assert!(check.update(true));
+    }
+
+    #[test]
+    fn test_debounce_default() {
+        let check = Check::new(
+            CheckData::Altitude(FloatCondition::LessThan(300.0)),
+            None,
+            0,
+        );
+
+        assert!(!check.update(false));
+        assert!(check.update(true));
+    }
+
+    #[test]
+    fn test_grouped_checks() {
+        let main = State::new(1);
+
+        let mut altitude = Check::new(
+            CheckData::Altitude(FloatCondition::LessThan(300.0)),
+            Some(StateTransition::Transition(&main)),
+            0,
+        );
+        altitude.group = Some(0);
+        let mut descending =
+            Check::new(CheckData::Velocity(FloatCondition::LessThan(0.0)), None, 1);
+        descending.group = Some(0);
+        let mut apogee = Check::new(CheckData::ApogeeFlag(NativeFlagCondition(true)), None, 2);
+        apogee.group = Some(0);
+
+        let checks = FrozenVec::new();
+        assert!(checks.push(&altitude).is_ok());
+        assert!(checks.push(&descending).is_ok());
+        assert!(checks.push(&apogee).is_ok());
+        let drogue = State::new_complete(0, checks, FrozenVec::new(), None);
+
+        let run = |satisfied: &[CheckKind]| {
+            drogue
+                .evaluate_checks(|data| satisfied.contains(&data.kind()))
+                .map(|(_, transition)| match transition {
+                    StateTransition::Transition(s) | StateTransition::Abort(s, _) => s.id,
+                })
+        };
+
+        assert_eq!(run(&[CheckKind::Altitude]), None);
+        assert_eq!(run(&[CheckKind::Altitude, CheckKind::Velocity]), None);
+        assert_eq!(run(&[CheckKind::Velocity, CheckKind::ApogeeFlag]), None);
+        assert_eq!(
+            run(&[
+                CheckKind::Altitude,
+                CheckKind::Velocity,
+                CheckKind::ApogeeFlag
+            ]),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_triggering_check_index() {
+        let descent = State::new(1);
+        let safe = State::new(2);
+
+        let apogee = Check::new(
+            CheckData::ApogeeFlag(NativeFlagCondition(true)),
+            Some(StateTransition::Transition(&descent)),
+            0,
+        );
+        let altitude = Check::new(
+            CheckData::Altitude(FloatCondition::LessThan(300.0)),
+            Some(StateTransition::Transition(&descent)),
+            1,
+        );
+        let tilt = Check::new(
+            CheckData::Tilt(FloatCondition::GreaterThan(30.0)),
+            Some(StateTransition::Abort(&safe, AbortReason::TiltExceeded)),
+            2,
+        );
+
+        // Stored highest priority first, as the conversion does, which is not the config order
+        let checks = FrozenVec::new();
+        assert!(checks.push(&tilt).is_ok());
+        assert!(checks.push(&apogee).is_ok());
+        assert!(checks.push(&altitude).is_ok());
+        let state = State::new_complete(0, checks, FrozenVec::new(), None);
+
+        let fired = |kind| {
+            state
+                .evaluate_checks(|data| data.kind() == kind)
+                .map(|(i, _)| i)
+        };
+        assert_eq!(fired(CheckKind::ApogeeFlag), Some(0));
+        assert_eq!(fired(CheckKind::Altitude), Some(1));
+        assert_eq!(fired(CheckKind::Tilt), Some(2));
+        assert_eq!(fired(CheckKind::Velocity), None);
+    }
+
+    #[test]
+    fn test_abort_reason() {
+        let safe = State::new(1);
+        let check = Check::new(
+            CheckData::Pyro1Continuity(PyroContinuityCondition(false)),
+            Some(StateTransition::Abort(&safe, AbortReason::ContinuityLoss)),
+            0,
+        );
+
+        let checks = FrozenVec::new();
+        assert!(checks.push(&check).is_ok());
+        let state = State::new_complete(0, checks, FrozenVec::new(), None);
+
+        match state.evaluate_checks(|_| true) {
+            Some((_, StateTransition::Abort(s, reason))) => {
+                assert_eq!(s.id, 1);
+                assert_eq!(reason, AbortReason::ContinuityLoss);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_low_battery_abort() {
+        let safe = State::new(1);
+        let check = Check::new(
+            CheckData::BatteryVoltage(FloatCondition::LessThan(7.0)),
+            Some(StateTransition::Abort(&safe, AbortReason::LowBattery)),
+            0,
+        );
+
+        let checks = FrozenVec::new();
+        assert!(checks.push(&check).is_ok());
+        let arm = State::new_complete(0, checks, FrozenVec::new(), None);
+
+        let run = |voltage| {
+            arm.evaluate_checks(|data| match data {
+                CheckData::BatteryVoltage(condition) => condition.is_satisfied(voltage),
+                _ => false,
+            })
+        };
+
+        assert!(run(8.2).is_none());
+        match run(6.5) {
+            Some((_, StateTransition::Abort(s, reason))) => {
+                assert_eq!(s.id, 1);
+                assert_eq!(reason, AbortReason::LowBattery);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_tilt_lockout() {
+        let sustainer = State::new(1);
+        let mut burnout = Check::new(
+            CheckData::BurnoutFlag(NativeFlagCondition(true)),
+            Some(StateTransition::Transition(&sustainer)),
+            0,
+        );
+        burnout.group = Some(0);
+        let mut upright = Check::new(CheckData::Tilt(FloatCondition::LessThan(20.0)), None, 1);
+        upright.group = Some(0);
+        assert_eq!(upright.data.kind(), CheckKind::Tilt);
+
+        let checks = FrozenVec::new();
+        assert!(checks.push(&burnout).is_ok());
+        assert!(checks.push(&upright).is_ok());
+        let boost = State::new_complete(0, checks, FrozenVec::new(), None);
+
+        // Burnout has been detected, so only the tilt decides whether the sustainer lights
+        let run = |tilt| {
+            boost.evaluate_checks(|data| match data.kind() {
+                CheckKind::BurnoutFlag => data.is_satisfied(ObjectState::Flag(true)),
+                CheckKind::Tilt => data.is_satisfied(ObjectState::Float(tilt)),
+                _ => false,
+            })
+        };
+
+        assert!(run(45.0).is_none());
+        match run(5.0) {
+            Some((0, StateTransition::Transition(s))) => assert_eq!(s.id, 1),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_min_dwell() {
+        let safe = State::new(1);
+        let check = Check::new(
+            CheckData::Altitude(FloatCondition::GreaterThan(0.0)),
+            Some(StateTransition::Transition(&safe)),
+            0,
+        );
+
+        let checks = FrozenVec::new();
+        assert!(checks.push(&check).is_ok());
+        let mut state = State::new_complete(0, checks, FrozenVec::new(), None);
+        state.min_dwell = Seconds(2.0);
+
+        // The check is satisfied immediately, but the transition is held off until the dwell
+        let transitions: heapless::Vec<bool, 4> = [0.0, 1.0, 1.9, 2.5]
+            .iter()
+            .map(|&elapsed| {
+                state.dwell_elapsed(elapsed) && state.evaluate_checks(|_| true).is_some()
+            })
+            .collect();
+
+        assert_eq!(transitions.as_slice(), &[false, false, false, true]);
+    }
+
+    #[test]
+    fn test_reset_commands() {
+        let one_shot = Command::new(CommandObject::Pyro1(true), Seconds(0.0));
+        let mut repeatable = Command::new(CommandObject::Beacon(true), Seconds(0.0));
+        repeatable.repeat = Repeat::OnReentry;
+
+        let commands = FrozenVec::new();
+        commands.push(&one_shot).unwrap();
+        commands.push(&repeatable).unwrap();
+        let state = State::new_complete(0, FrozenVec::new(), commands, None);
+
+        // The first entry fires both commands once
+        state.reset_commands();
+        assert!(matches!(
+            state.poll_command(&one_shot, 0.0, |_| true),
+            CommandAction::Execute
+        ));
+        assert!(repeatable.poll(0.0));
+        assert!(matches!(
+            state.poll_command(&one_shot, 1.0, |_| true),
+            CommandAction::Wait
+        ));
+        assert!(!repeatable.poll(1.0));
+
+        // Re-entering fires only the command that repeats on re-entry
+        state.reset_commands();
+        assert!(matches!(
+            state.poll_command(&one_shot, 0.0, |_| true),
+            CommandAction::Wait
+        ));
+        assert!(repeatable.poll(0.0));
+        assert!(!repeatable.poll(1.0));
+    }
+
+    #[test]
+    fn test_periodic_command() {
+        let one_shot = Command::new(CommandObject::Pyro1(true), Seconds(0.5));
+        let mut beacon = Command::new(CommandObject::Beacon(true), Seconds(0.5));
+        beacon.repeat = Repeat::Periodic;
+
+        let commands = FrozenVec::new();
+        commands.push(&one_shot).unwrap();
+        commands.push(&beacon).unwrap();
+        let state = State::new_complete(0, FrozenVec::new(), commands, None);
+        state.reset_commands();
+
+        // Advance a mock clock in 0.1s steps for just over 2 seconds
+        let mut one_shot_fires = 0;
+        let mut beacon_fires: heapless::Vec<u32, 8> = heapless::Vec::new();
+        for tick in 0..=21 {
+            let elapsed = tick as f32 * 0.1;
+            if one_shot.poll(elapsed) {
+                one_shot_fires += 1;
+            }
+            if beacon.poll(elapsed) {
+                beacon_fires.push(tick).unwrap();
+            }
+        }
+
+        assert_eq!(one_shot_fires, 1);
+        // Each interval is measured from the previous fire, so float rounding shows up as the
+        // occasional late tick rather than accumulating
+        assert_eq!(beacon_fires.len(), 4);
+        for pair in beacon_fires.windows(2) {
+            assert!((5..=6).contains(&(pair[1] - pair[0])));
+        }
+
+        // Re-entering the state restarts the period
+        state.reset_commands();
+        assert!(!beacon.poll(0.4));
+        assert!(beacon.poll(0.5));
+    }
+
+    #[test]
+    fn test_snapshot_fetches_once() {
+        let low = Check::new(
+            CheckData::Altitude(FloatCondition::LessThan(100.0)),
+            None,
+            0,
+        );
+        let high = Check::new(
+            CheckData::Altitude(FloatCondition::GreaterThan(50.0)),
+            None,
+            1,
+        );
+        let rising = Check::new(
+            CheckData::Velocity(FloatCondition::GreaterThan(0.0)),
+            None,
+            2,
+        );
+        let checks = FrozenVec::new();
+        assert!(checks.push(&low).is_ok());
+        assert!(checks.push(&high).is_ok());
+        assert!(checks.push(&rising).is_ok());
+        let state = State::new_complete(0, checks, FrozenVec::new(), None);
+
+        // A mock workspace which counts how many times each kind is read
+        let mut fetches: heapless::Vec<CheckKind, 8> = heapless::Vec::new();
+        for _ in 0..2 {
+            let snapshot = state.snapshot(|kind| {
+                fetches.push(kind).unwrap();
+                Some(ObjectState::Float(75.0))
+            });
+            state.evaluate_checks(|data| snapshot.satisfies(data));
+            assert!(snapshot.satisfies(&low.data) && snapshot.satisfies(&high.data));
+            assert_eq!(snapshot.get(CheckKind::Tilt), None);
+        }
+
+        // Altitude and velocity are each read once per execution, however many checks use them
+        assert_eq!(
+            fetches.as_slice(),
+            &[
+                CheckKind::Altitude,
+                CheckKind::Velocity,
+                CheckKind::Altitude,
+                CheckKind::Velocity
+            ]
+        );
+    }
+
+    #[test]
+    fn test_continuity_guard() {
+        let drogue = Command::new(CommandObject::Pyro1(true), Seconds(1.0));
+        let beacon = Command::new(CommandObject::Beacon(true), Seconds(1.0));
+        let commands = FrozenVec::new();
+        commands.push(&drogue).unwrap();
+        commands.push(&beacon).unwrap();
+
+        let safe = State::new(1);
+        let state = State::new_complete(0, FrozenVec::new(), commands, None);
+        let abort = StateTransition::Abort(&safe, AbortReason::ContinuityLoss);
+        state
+            .continuity_guard
+            .set(Some(super::ContinuityGuard::new(2.0, abort)));
+
+        assert!(matches!(
+            state.poll_command(&drogue, 0.5, |_| true),
+            CommandAction::Wait
+        ));
+
+        // The beacon is not a pyro channel, so it doesn't wait for continuity
+        assert!(matches!(
+            state.poll_command(&beacon, 1.0, |_| false),
+            CommandAction::Execute
+        ));
+
+        // Due, but the igniter is not connected yet
+        for elapsed in [1.0, 2.0, 2.9] {
+            let action = state.poll_command(&drogue, elapsed, |kind| {
+                assert_eq!(kind, CheckKind::Pyro1Continuity);
+                false
+            });
+            assert!(matches!(action, CommandAction::Wait));
+        }
+        assert!(!drogue.was_executed.load(Ordering::Relaxed));
+
+        // Continuity appears before the deadline, so the command fires
+        assert!(matches!(
+            state.poll_command(&drogue, 2.95, |_| true),
+            CommandAction::Execute
+        ));
+        assert!(drogue.was_executed.load(Ordering::Relaxed));
+
+        // Once it never appears, the deadline aborts
+        let main = Command::new(CommandObject::Pyro2(true), Seconds(1.0));
+        match state.poll_command(&main, 3.0, |_| false) {
+            CommandAction::Abort(StateTransition::Abort(to, reason)) => {
+                assert_eq!(to.id, 1);
+                assert_eq!(reason, AbortReason::ContinuityLoss);
+            }
+            _ => panic!("expected a continuity abort"),
         }
     }
 }
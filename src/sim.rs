@@ -0,0 +1,639 @@
+//! A host-side simulator which replays a flight through a config, so that a config can be tested
+//! end to end before it is flown.
+//!
+//! The simulator runs the [`reference`] states the same way the flight computer does, but reads
+//! each check's value from a recorded or synthetic [`Sample`] instead of from the sensors, and
+//! records what it would have done as [`Event`]s instead of driving any hardware.
+
+use crate::conversions::{indices_to_refs, ConversionError};
+use crate::detection::{descent_rate, STANDARD_GRAVITY};
+use crate::log::{LogRecord, LogSink, RecordPayload};
+use crate::reference::{CommandAction, State, StateTransition};
+use crate::workspace::Workspace;
+use crate::{index, AbortReason, CheckData, CheckKind, CommandObject, ObjectState, Seconds};
+
+use alloc_traits::LocalAlloc;
+use std::vec::Vec;
+
+/// Everything the checks in a config can read, at one point in a flight
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Sample {
+    /// Seconds since boot
+    pub time: f32,
+    /// Meters above the ground
+    pub altitude: f32,
+    /// Meters per second, up positive
+    pub velocity: f32,
+    /// Meters per second squared, up positive
+    pub acceleration: f32,
+    /// Degrees from vertical
+    pub tilt: f32,
+    /// Volts
+    pub battery_voltage: f32,
+    pub apogee: bool,
+    pub burnout: bool,
+    pub landed: bool,
+    pub mach_lock: bool,
+    /// Whether each pyro channel, from 1 to 3, has continuity
+    pub continuity: [bool; 3],
+    /// Seconds since boot at which liftoff was detected, if it has been
+    pub liftoff_time: Option<f32>,
+}
+
+impl Sample {
+    /// Returns the value a check of `kind` reads from this sample, or `None` if it has no value
+    /// yet, such as the time since liftoff while still on the pad
+    pub fn get(&self, kind: CheckKind) -> Option<ObjectState> {
+        Some(match kind {
+            CheckKind::Altitude => ObjectState::Float(self.altitude),
+            CheckKind::Velocity => ObjectState::Float(self.velocity),
+            CheckKind::Acceleration => ObjectState::Float(self.acceleration),
+            CheckKind::Tilt => ObjectState::Float(self.tilt),
+            CheckKind::ApogeeFlag => ObjectState::Flag(self.apogee),
+            CheckKind::Pyro1Continuity => ObjectState::Flag(self.continuity[0]),
+            CheckKind::Pyro2Continuity => ObjectState::Flag(self.continuity[1]),
+            CheckKind::Pyro3Continuity => ObjectState::Flag(self.continuity[2]),
+            CheckKind::LandedFlag => ObjectState::Flag(self.landed),
+            CheckKind::BurnoutFlag => ObjectState::Flag(self.burnout),
+            CheckKind::MachLockFlag => ObjectState::Flag(self.mach_lock),
+            CheckKind::DescentRate => ObjectState::Float(descent_rate(self.velocity)),
+            CheckKind::BatteryVoltage => ObjectState::Float(self.battery_voltage),
+            CheckKind::TimeSinceBoot => ObjectState::Float(self.time),
+            CheckKind::TimeSinceLiftoff => ObjectState::Float(self.time - self.liftoff_time?),
+        })
+    }
+
+    /// Returns true if `data` is satisfied by this sample
+    pub fn satisfies(&self, data: &CheckData) -> bool {
+        self.get(data.kind())
+            .is_some_and(|value| data.is_satisfied(value))
+    }
+}
+
+/// A sample stands in for the flight computer's [`DataWorkspace`](crate::workspace::DataWorkspace)
+impl Workspace for Sample {
+    fn get_object(&self, kind: CheckKind) -> Option<ObjectState> {
+        self.get(kind)
+    }
+}
+
+/// Something the flight computer did during a simulated flight
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Event {
+    /// The state at index `from` transitioned to the state at index `to`
+    Transition {
+        time: f32,
+        from: u8,
+        to: u8,
+        abort: Option<AbortReason>,
+    },
+    /// A command was executed, including a pyro being turned off at the end of its pulse
+    Command { time: f32, object: CommandObject },
+}
+
+/// Runs a config against a sequence of [`Sample`]s
+pub struct Simulator {
+    states: &'static [State<'static>],
+    current: &'static State<'static>,
+    entered_at: f32,
+    /// Pyros which are turned off at the end of a pulse, and when
+    pending_off: Vec<(f32, CommandObject)>,
+    events: Vec<Event>,
+}
+
+impl Simulator {
+    /// Converts `config` to reference states allocated from `alloc`, and enters its default state
+    /// at boot
+    pub fn new(
+        config: &index::ConfigFile,
+        alloc: &'static dyn LocalAlloc<'static>,
+    ) -> Result<Self, ConversionError> {
+        let states = indices_to_refs(config, alloc)?;
+        let current = &states[usize::from(config.default_state)];
+        current.reset_commands();
+        current.reset_checks();
+
+        Ok(Self {
+            states,
+            current,
+            entered_at: 0.0,
+            pending_off: Vec::new(),
+            events: Vec::new(),
+        })
+    }
+
+    /// Returns the index of the active state
+    pub fn current_state(&self) -> u8 {
+        self.current.id
+    }
+
+    /// Returns the reference states being run
+    pub fn states(&self) -> &'static [State<'static>] {
+        self.states
+    }
+
+    /// Returns everything that has happened so far, in order
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Returns the time of the first execution of `object`, if it was ever executed
+    pub fn first_command(&self, object: CommandObject) -> Option<f32> {
+        self.events.iter().find_map(|event| match *event {
+            Event::Command { time, object: o } if o == object => Some(time),
+            _ => None,
+        })
+    }
+
+    /// Runs every sample in order
+    pub fn run<I: IntoIterator<Item = Sample>>(&mut self, samples: I) {
+        for sample in samples {
+            self.step(&sample);
+        }
+    }
+
+    /// Runs every sample in order, writing a record of each transition to `log`
+    pub fn run_logged<I, S>(&mut self, samples: I, log: &mut S)
+    where
+        I: IntoIterator<Item = Sample>,
+        S: LogSink,
+    {
+        for sample in samples {
+            self.step_logged(&sample, log);
+        }
+    }
+
+    /// Runs one execution of the active state with the values in `sample`.
+    ///
+    /// Due commands are executed first, and a pyro command that has waited too long for
+    /// continuity aborts straight away. Otherwise, once the state's minimum dwell has passed, a
+    /// sensor fault, the state's checks, and finally its timeout are checked for a transition. A
+    /// state that is entered executes its commands which are due immediately in the same step.
+    pub fn step(&mut self, sample: &Sample) {
+        self.step_with_log(sample, None::<&mut NoLog>);
+    }
+
+    /// Runs one execution of the active state like [`step`](Self::step), writing a record to
+    /// `log` if it transitions
+    pub fn step_logged<S: LogSink>(&mut self, sample: &Sample, log: &mut S) {
+        self.step_with_log(sample, Some(log));
+    }
+
+    fn step_with_log<S: LogSink>(&mut self, sample: &Sample, log: Option<&mut S>) {
+        self.end_pulses(sample.time);
+        let transition = match self.execute_commands(sample) {
+            Some(abort) => Some(abort),
+            None => self.check_transition(sample),
+        };
+
+        if let Some(transition) = transition {
+            self.transition(sample.time, transition, log);
+            // A command left waiting for continuity is polled again, and can abort, next step
+            let _ = self.execute_commands(sample);
+        }
+    }
+
+    /// Returns the transition the active state's checks or timeout make with `sample`, if any
+    fn check_transition(&self, sample: &Sample) -> Option<StateTransition<'static>> {
+        let state = self.current;
+        let elapsed = sample.time - self.entered_at;
+        if !state.dwell_elapsed(elapsed) {
+            return None;
+        }
+
+        let snapshot = state.snapshot(|kind| sample.get(kind));
+        match state.fault_transition.get() {
+            Some(transition) if snapshot.has_invalid() => Some(transition),
+            _ => state
+                .evaluate_checks(|data| snapshot.satisfies(data))
+                .map(|(_, transition)| transition)
+                .or_else(|| {
+                    let timeout = state.timeout.get()?;
+                    (elapsed >= timeout.time).then_some(timeout.transition)
+                }),
+        }
+    }
+
+    /// Enters the state `transition` goes to, writing a record of it to `log` if one is given
+    fn transition<S: LogSink>(
+        &mut self,
+        time: f32,
+        transition: StateTransition<'static>,
+        log: Option<&mut S>,
+    ) {
+        let (to, abort) = match transition {
+            StateTransition::Transition(to) => (to, None),
+            StateTransition::Abort(to, reason) => (to, Some(reason)),
+        };
+        self.events.push(Event::Transition {
+            time,
+            from: self.current.id,
+            to: to.id,
+            abort,
+        });
+        if let Some(log) = log {
+            let record = LogRecord::new(
+                Seconds(time).as_millis(),
+                RecordPayload::StateTransition {
+                    from: self.current.id,
+                    to: to.id,
+                    abort,
+                },
+            );
+            // As in flight, a record that can't be written is dropped and the flight carries on
+            let _ = log.write(&record);
+        }
+
+        self.current = to;
+        self.entered_at = time;
+        to.reset_commands();
+        to.reset_checks();
+    }
+
+    /// Executes every due command, returning the abort to make if a pyro command has waited too
+    /// long for continuity
+    fn execute_commands(&mut self, sample: &Sample) -> Option<StateTransition<'static>> {
+        let time = sample.time;
+        let state = self.current;
+        let elapsed = time - self.entered_at;
+        for command in state.commands.iter() {
+            let has_continuity = |kind| sample.get(kind) == Some(ObjectState::Flag(true));
+            match state.poll_command(command, elapsed, has_continuity) {
+                CommandAction::Wait => continue,
+                CommandAction::Abort(abort) => return Some(abort),
+                CommandAction::Execute => {}
+            }
+            self.events.push(Event::Command {
+                time,
+                object: command.object,
+            });
+
+            if let Some(pulse) = command.pulse {
+                if let Ok(off) = command.object.kind().try_with_bool(false) {
+                    self.pending_off.push((time + pulse.0, off));
+                }
+            }
+        }
+        None
+    }
+
+    fn end_pulses(&mut self, time: f32) {
+        let events = &mut self.events;
+        self.pending_off.retain(|&(off_at, object)| {
+            if time < off_at {
+                return true;
+            }
+            events.push(Event::Command { time, object });
+            false
+        });
+    }
+}
+
+/// The sink a [`Simulator`] stepped without a log is typed with, which is never written to
+struct NoLog;
+
+impl LogSink for NoLog {
+    type Error = core::convert::Infallible;
+
+    fn write(&mut self, _record: &LogRecord) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// A simple synthetic flight, which boosts straight up at a constant acceleration, coasts to
+/// apogee, then descends at a fixed rate under drogue and main until it lands
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FlightProfile {
+    /// Seconds on the pad after boot before liftoff
+    pub pad_time: f32,
+    /// Acceleration from the motor in m/s², not including gravity
+    pub boost_acceleration: f32,
+    /// Seconds the motor burns for
+    pub burn_time: f32,
+    /// Meters per second the rocket descends at under drogue
+    pub drogue_descent_rate: f32,
+    /// Meters above the ground at which the descent slows to `main_descent_rate`
+    pub main_altitude: f32,
+    /// Meters per second the rocket descends at under main
+    pub main_descent_rate: f32,
+    /// Degrees the rocket is tilted from vertical, for the whole flight
+    pub tilt: f32,
+    /// If the apogee flag is ever set, which can be cleared to simulate a failed detection
+    pub detects_apogee: bool,
+    /// Seconds between samples
+    pub dt: f32,
+}
+
+impl Default for FlightProfile {
+    fn default() -> Self {
+        Self {
+            pad_time: 1.0,
+            boost_acceleration: 60.0,
+            burn_time: 3.0,
+            drogue_descent_rate: 25.0,
+            main_altitude: 300.0,
+            main_descent_rate: 6.0,
+            tilt: 2.0,
+            detects_apogee: true,
+            dt: 0.01,
+        }
+    }
+}
+
+impl FlightProfile {
+    /// Returns a sample every `dt` seconds from boot until a few seconds after landing
+    pub fn samples(&self) -> Vec<Sample> {
+        let mut samples = Vec::new();
+        let mut sample = Sample {
+            battery_voltage: 8.4,
+            tilt: self.tilt,
+            continuity: [true; 3],
+            ..Sample::default()
+        };
+        let mut past_apogee = false;
+        let mut landed_at = None;
+
+        for tick in 0.. {
+            let time = tick as f32 * self.dt;
+            sample.time = time;
+
+            if time < self.pad_time {
+                sample.acceleration = 0.0;
+            } else if !past_apogee {
+                sample.liftoff_time = Some(self.pad_time);
+                let boosting = time < self.pad_time + self.burn_time;
+                sample.burnout = !boosting;
+                sample.acceleration = if boosting {
+                    self.boost_acceleration - STANDARD_GRAVITY
+                } else {
+                    -STANDARD_GRAVITY
+                };
+                sample.velocity += sample.acceleration * self.dt;
+                sample.altitude += sample.velocity * self.dt;
+                past_apogee = !boosting && sample.velocity <= 0.0;
+                sample.apogee = past_apogee && self.detects_apogee;
+            } else if landed_at.is_none() {
+                let rate = if sample.altitude > self.main_altitude {
+                    self.drogue_descent_rate
+                } else {
+                    self.main_descent_rate
+                };
+                sample.acceleration = 0.0;
+                sample.velocity = -rate;
+                sample.altitude += sample.velocity * self.dt;
+                if sample.altitude <= 0.0 {
+                    sample.altitude = 0.0;
+                    sample.velocity = 0.0;
+                    landed_at = Some(time);
+                }
+            }
+
+            if let Some(landed_at) = landed_at {
+                sample.landed = time - landed_at >= 1.0;
+                if time - landed_at > 3.0 {
+                    break;
+                }
+            }
+            samples.push(sample);
+        }
+
+        samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, FlightProfile, Simulator};
+    use crate::builder::{ConfigBuilder, StateBuilder, Transition};
+    use crate::detection::STANDARD_GRAVITY;
+    use crate::log::{LogRecord, RecordPayload};
+    use crate::{
+        AbortReason, CheckData, CommandObject, FloatCondition, NativeFlagCondition, Seconds,
+    };
+
+    use static_alloc::Bump;
+
+    /// Gives each simulation its own allocator, since a bump allocator never frees and the tests
+    /// run in parallel
+    fn allocator() -> &'static Bump<[u8; 1 << 14]> {
+        Box::leak(Box::new(Bump::uninit()))
+    }
+
+    #[test]
+    fn test_flight_profile() {
+        let samples = FlightProfile::default().samples();
+        let apogee = samples.iter().find(|sample| sample.apogee).unwrap();
+        // Apogee is the burnout altitude, plus the height coasted at burnout velocity v of v²/2g
+        let net_acceleration = 60.0 - STANDARD_GRAVITY;
+        let burnout_velocity = net_acceleration * 3.0;
+        let expected = 0.5 * net_acceleration * 3.0 * 3.0
+            + burnout_velocity * burnout_velocity / (2.0 * STANDARD_GRAVITY);
+        assert!(
+            (apogee.altitude - expected).abs() < 5.0,
+            "{}",
+            apogee.altitude
+        );
+
+        let last = samples.last().unwrap();
+        assert!(last.landed && last.altitude == 0.0);
+        assert_eq!(samples[0].liftoff_time, None);
+        assert_eq!(last.liftoff_time, Some(1.0));
+    }
+
+    #[test]
+    fn test_boost_coast_descent() {
+        let config = ConfigBuilder::new(0)
+            .state(StateBuilder::new(0).check(
+                CheckData::Acceleration(FloatCondition::GreaterThan(2.0 * STANDARD_GRAVITY)),
+                Some(Transition::To(1)),
+            ))
+            .state(StateBuilder::new(1).check(
+                CheckData::BurnoutFlag(NativeFlagCondition(true)),
+                Some(Transition::To(2)),
+            ))
+            .state(StateBuilder::new(2).check(
+                CheckData::ApogeeFlag(NativeFlagCondition(true)),
+                Some(Transition::To(3)),
+            ))
+            .state(
+                StateBuilder::new(3)
+                    .command(CommandObject::Pyro1(true), Seconds(0.0))
+                    .check(
+                        CheckData::Altitude(FloatCondition::LessThan(300.0)),
+                        Some(Transition::To(4)),
+                    ),
+            )
+            .state(
+                StateBuilder::new(4)
+                    .command(CommandObject::Pyro2(true), Seconds(0.0))
+                    .terminal(),
+            )
+            .build()
+            .unwrap();
+
+        let samples = FlightProfile::default().samples();
+        let mut sim = Simulator::new(&config, allocator()).unwrap();
+        sim.run(samples.iter().copied());
+
+        let timeline: std::vec::Vec<_> = sim
+            .events()
+            .iter()
+            .filter_map(|event| match *event {
+                Event::Transition { from, to, .. } => Some((from, to)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(timeline, [(0, 1), (1, 2), (2, 3), (3, 4)]);
+
+        // The drogue fires within a tenth of a second of apogee, and the main after it once below
+        // 300m
+        let apogee = samples.iter().find(|sample| sample.apogee).unwrap();
+        let drogue = sim.first_command(CommandObject::Pyro1(true)).unwrap();
+        assert!(drogue >= apogee.time && drogue - apogee.time < 0.1);
+
+        let main = sim.first_command(CommandObject::Pyro2(true)).unwrap();
+        let main_sample = samples.iter().find(|sample| sample.time == main).unwrap();
+        assert!(main > drogue && main_sample.altitude < 300.0);
+    }
+
+    #[test]
+    fn test_transition_log() {
+        let config = ConfigBuilder::new(0)
+            .state(StateBuilder::new(0).timeout(1.0, Transition::To(1)))
+            .state(StateBuilder::new(1).check(
+                CheckData::Altitude(FloatCondition::GreaterThan(100.0)),
+                Some(Transition::Abort(2, AbortReason::Manual)),
+            ))
+            .state(StateBuilder::new(2).terminal())
+            .build()
+            .unwrap();
+
+        let mut log: heapless::Vec<LogRecord, 8> = heapless::Vec::new();
+        let mut sim = Simulator::new(&config, allocator()).unwrap();
+        sim.run_logged(FlightProfile::default().samples(), &mut log);
+
+        // One record for each transition, with the same ids, abort, and time
+        let transitions: std::vec::Vec<_> = sim
+            .events()
+            .iter()
+            .filter_map(|event| match *event {
+                Event::Transition {
+                    time,
+                    from,
+                    to,
+                    abort,
+                } => Some(LogRecord::new(
+                    Seconds(time).as_millis(),
+                    RecordPayload::StateTransition { from, to, abort },
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(transitions.len(), 2);
+        assert_eq!(log.as_slice(), transitions.as_slice());
+        assert_eq!(
+            log[1].payload,
+            RecordPayload::StateTransition {
+                from: 1,
+                to: 2,
+                abort: Some(AbortReason::Manual),
+            }
+        );
+    }
+
+    #[test]
+    fn test_simulator() {
+        let config = ConfigBuilder::new(0)
+            .state(
+                StateBuilder::new(0)
+                    .check(
+                        CheckData::Tilt(FloatCondition::GreaterThan(30.0)),
+                        Some(Transition::Abort(2, AbortReason::TiltExceeded)),
+                    )
+                    .timeout(2.0, Transition::To(1)),
+            )
+            .state(
+                StateBuilder::new(1)
+                    .pulse(CommandObject::Pyro3(true), Seconds(0.5), Seconds(1.0))
+                    .terminal(),
+            )
+            .state(StateBuilder::new(2).terminal())
+            .build()
+            .unwrap();
+
+        let mut sim = Simulator::new(&config, allocator()).unwrap();
+        sim.run(
+            FlightProfile {
+                dt: 0.25,
+                ..FlightProfile::default()
+            }
+            .samples(),
+        );
+        assert_eq!(sim.current_state(), 1);
+        assert_eq!(
+            sim.events()[..3],
+            [
+                Event::Transition {
+                    time: 2.0,
+                    from: 0,
+                    to: 1,
+                    abort: None,
+                },
+                Event::Command {
+                    time: 2.5,
+                    object: CommandObject::Pyro3(true),
+                },
+                Event::Command {
+                    time: 3.5,
+                    object: CommandObject::Pyro3(false),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_continuity_guard() {
+        let config = ConfigBuilder::new(0)
+            .continuity_guard(Seconds(1.0), 2)
+            .state(StateBuilder::new(0).timeout(2.0, Transition::To(1)))
+            .state(
+                StateBuilder::new(1)
+                    .command(CommandObject::Pyro3(true), Seconds(0.5))
+                    .terminal(),
+            )
+            .state(StateBuilder::new(2).terminal())
+            .build()
+            .unwrap();
+        let profile = FlightProfile {
+            dt: 0.25,
+            ..FlightProfile::default()
+        };
+
+        // The igniter is connected half a second after the command is due, so it fires late
+        let mut sim = Simulator::new(&config, allocator()).unwrap();
+        sim.run(profile.samples().into_iter().map(|mut sample| {
+            sample.continuity[2] = sample.time >= 3.0;
+            sample
+        }));
+        assert_eq!(sim.current_state(), 1);
+        assert_eq!(sim.first_command(CommandObject::Pyro3(true)), Some(3.0));
+
+        // The igniter is never connected, so the command aborts once it has waited a second
+        let mut sim = Simulator::new(&config, allocator()).unwrap();
+        sim.run(profile.samples().into_iter().map(|mut sample| {
+            sample.continuity[2] = false;
+            sample
+        }));
+        assert_eq!(sim.current_state(), 2);
+        assert_eq!(sim.first_command(CommandObject::Pyro3(true)), None);
+        assert_eq!(
+            sim.events().last(),
+            Some(&Event::Transition {
+                time: 3.5,
+                from: 1,
+                to: 2,
+                abort: Some(AbortReason::ContinuityLoss),
+            })
+        );
+    }
+}
@@ -0,0 +1,662 @@
+//! Serialization of [`index::ConfigFile`]s for storage in flash, where a bad block or a partial
+//! write can corrupt them.
+//!
+//! A stored config is laid out as:
+//!
+//! | Bytes | Contents                                             |
+//! |-------|------------------------------------------------------|
+//! | 4     | [`CONFIG_MAGIC`]                                     |
+//! | 2     | [`CONFIG_VERSION`], little endian                    |
+//! | 2     | Length of the serialized config, little endian       |
+//! | N     | The config, serialized with `postcard`               |
+//! | 4     | CRC-32 of all of the preceding bytes, little endian  |
+
+use crate::index::{self, ConfigError};
+use crate::{MAX_CHECKS_PER_STATE, MAX_COMMANDS_PER_STATE, MAX_STATES};
+
+use core::fmt;
+use core::marker::PhantomData;
+use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
+
+/// Marks the start of a stored config
+pub const CONFIG_MAGIC: [u8; 4] = *b"NOVA";
+
+/// The version of the config layout this firmware understands. This must be bumped whenever the
+/// serialized form of [`index::ConfigFile`] changes
+pub const CONFIG_VERSION: u16 = 6;
+
+/// The size of a page in the W25N512GV's main array. A stored config must fit within one page
+pub const PAGE_SIZE: usize = 2048;
+
+const MAGIC_SIZE: usize = 4;
+const VERSION_SIZE: usize = 2;
+const LEN_SIZE: usize = 2;
+const HEADER_SIZE: usize = MAGIC_SIZE + VERSION_SIZE + LEN_SIZE;
+const CRC_SIZE: usize = 4;
+
+/// An error encountered while storing or loading a config
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StorageError {
+    /// The buffer is too small to hold the stored config
+    BufferTooSmall,
+    /// The buffer does not start with [`CONFIG_MAGIC`], so it does not hold a config
+    BadMagic,
+    /// The config was stored with a layout version this firmware does not understand
+    UnsupportedVersion { found: u16, expected: u16 },
+    /// The config could not be serialized
+    Serialize,
+    /// The stored bytes passed verification but did not deserialize into a config
+    Deserialize,
+    /// The stored CRC does not match the stored bytes, so they are corrupt
+    ChecksumMismatch { stored: u32, computed: u32 },
+    /// A page read back after being written differs from what was written, first at `offset`
+    VerifyMismatch { offset: usize },
+    /// The stored config is well formed, but has more states, checks, or commands than fit
+    Config(ConfigError),
+}
+
+/// A flash device which a config page can be written to and read back from, such as the
+/// W25N512GV
+pub trait PageFlash {
+    type Error;
+
+    /// Programs `page` into the flash page reserved for the config
+    fn write_page(&mut self, page: &[u8; PAGE_SIZE]) -> Result<(), Self::Error>;
+
+    /// Reads the flash page reserved for the config into `page`
+    fn read_page(&mut self, page: &mut [u8; PAGE_SIZE]) -> Result<(), Self::Error>;
+}
+
+/// An error encountered while writing a config to a [`PageFlash`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FlashError<E> {
+    /// The config could not be stored, or the copy read back failed verification
+    Storage(StorageError),
+    /// The flash device itself reported an error
+    Flash(E),
+}
+
+impl<E> From<StorageError> for FlashError<E> {
+    fn from(error: StorageError) -> Self {
+        FlashError::Storage(error)
+    }
+}
+
+/// Computes the standard CRC-32 (IEEE 802.3, as used by zlib and Ethernet) of `bytes`
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Serializes `config` into `buf` with a header and CRC, returning the number of bytes written
+pub fn store_config(config: &index::ConfigFile, buf: &mut [u8]) -> Result<usize, StorageError> {
+    store_config_with_version(config, CONFIG_VERSION, buf)
+}
+
+/// Stores any serializable `config`, so tests can store configs that `index::ConfigFile` can't hold
+fn store_config_with_version<T: serde::Serialize>(
+    config: &T,
+    version: u16,
+    buf: &mut [u8],
+) -> Result<usize, StorageError> {
+    if buf.len() < HEADER_SIZE + CRC_SIZE {
+        return Err(StorageError::BufferTooSmall);
+    }
+
+    let payload_space = buf.len() - CRC_SIZE;
+    let payload_len = postcard::to_slice(config, &mut buf[HEADER_SIZE..payload_space])
+        .map_err(|e| match e {
+            postcard::Error::SerializeBufferFull => StorageError::BufferTooSmall,
+            _ => StorageError::Serialize,
+        })?
+        .len();
+    let len = u16::try_from(payload_len).map_err(|_| StorageError::Serialize)?;
+    buf[..MAGIC_SIZE].copy_from_slice(&CONFIG_MAGIC);
+    buf[MAGIC_SIZE..MAGIC_SIZE + VERSION_SIZE].copy_from_slice(&version.to_le_bytes());
+    buf[MAGIC_SIZE + VERSION_SIZE..HEADER_SIZE].copy_from_slice(&len.to_le_bytes());
+
+    let crc_start = HEADER_SIZE + payload_len;
+    let crc = crc32(&buf[..crc_start]);
+    buf[crc_start..crc_start + CRC_SIZE].copy_from_slice(&crc.to_le_bytes());
+
+    Ok(crc_start + CRC_SIZE)
+}
+
+/// Stores `config` into a flash page buffer, returning the number of bytes used.
+///
+/// The rest of the page is left as `0xFF`, the value of erased flash, so it can be programmed
+/// without disturbing the unused bytes.
+pub fn store_config_page(
+    config: &index::ConfigFile,
+    page: &mut [u8; PAGE_SIZE],
+) -> Result<usize, StorageError> {
+    page.fill(0xFF);
+    store_config(config, page)
+}
+
+/// Loads a config stored in a flash page by [`store_config_page`]
+pub fn load_config_page(page: &[u8; PAGE_SIZE]) -> Result<index::ConfigFile, StorageError> {
+    load_config(page)
+}
+
+/// Writes `config` to `flash`, then reads it back into `readback` and verifies it, returning the
+/// number of bytes used.
+///
+/// A silent bad write would otherwise only be found at the next boot. This reads back the whole
+/// page, so it is only meant for the config and not for bulk log writes.
+pub fn write_config_verified<F: PageFlash>(
+    flash: &mut F,
+    config: &index::ConfigFile,
+    page: &mut [u8; PAGE_SIZE],
+    readback: &mut [u8; PAGE_SIZE],
+) -> Result<usize, FlashError<F::Error>> {
+    let len = store_config_page(config, page)?;
+    flash.write_page(page).map_err(FlashError::Flash)?;
+    flash.read_page(readback).map_err(FlashError::Flash)?;
+
+    if let Some(offset) = page.iter().zip(readback.iter()).position(|(a, b)| a != b) {
+        return Err(StorageError::VerifyMismatch { offset }.into());
+    }
+    load_config_page(readback)?;
+
+    Ok(len)
+}
+
+/// Loads a config stored in a flash page by [`store_config_page`], or falls back to the config
+/// returned by `default` if the page is erased or fails verification. `default` is given the
+/// reason the stored config was rejected so it can be reported
+pub fn load_config_page_or_else<F>(page: &[u8; PAGE_SIZE], default: F) -> index::ConfigFile
+where
+    F: FnOnce(StorageError) -> index::ConfigFile,
+{
+    load_config_page(page).unwrap_or_else(default)
+}
+
+/// Verifies the header and CRC of a config stored in `buf` by [`store_config`] and deserializes it
+pub fn load_config(buf: &[u8]) -> Result<index::ConfigFile, StorageError> {
+    if buf.len() < HEADER_SIZE {
+        return Err(StorageError::BufferTooSmall);
+    }
+    if buf[..MAGIC_SIZE] != CONFIG_MAGIC {
+        return Err(StorageError::BadMagic);
+    }
+
+    let version = u16::from_le_bytes([buf[MAGIC_SIZE], buf[MAGIC_SIZE + 1]]);
+    let payload_len = u16::from_le_bytes([buf[HEADER_SIZE - 2], buf[HEADER_SIZE - 1]]) as usize;
+    let crc_start = HEADER_SIZE + payload_len;
+    let crc_bytes = buf
+        .get(crc_start..crc_start + CRC_SIZE)
+        .ok_or(StorageError::BufferTooSmall)?;
+
+    let stored = u32::from_le_bytes([crc_bytes[0], crc_bytes[1], crc_bytes[2], crc_bytes[3]]);
+    let computed = crc32(&buf[..crc_start]);
+    if stored != computed {
+        return Err(StorageError::ChecksumMismatch { stored, computed });
+    }
+
+    // The version is only trusted once the CRC shows it isn't corrupt
+    if version != CONFIG_VERSION {
+        return Err(StorageError::UnsupportedVersion {
+            found: version,
+            expected: CONFIG_VERSION,
+        });
+    }
+
+    let payload = &buf[HEADER_SIZE..crc_start];
+    postcard::from_bytes(payload).map_err(|_| match postcard::from_bytes(payload) {
+        Ok(ConfigShape {
+            states: StatesShape(Some(error)),
+            ..
+        }) => StorageError::Config(error),
+        _ => StorageError::Deserialize,
+    })
+}
+
+/// The layout of an [`index::ConfigFile`], which is decoded without storing its states so that a
+/// config which does not fit in the fixed bounds can be told apart from corrupt bytes. This must
+/// list the same fields in the same order as `ConfigFile`
+#[derive(serde::Deserialize)]
+struct ConfigShape {
+    _default_state: index::StateIndex,
+    states: StatesShape,
+    _continuity_guard: Option<index::ContinuityGuard>,
+}
+
+/// The layout of an [`index::State`], which must list the same fields in the same order
+#[derive(serde::Deserialize)]
+struct StateShape {
+    _name: Option<crate::StateName>,
+    checks: Count<index::Check>,
+    commands: Count<index::Command>,
+    _timeout: Option<index::Timeout>,
+    _fault_transition: Option<index::StateTransition>,
+    _min_dwell: crate::Seconds,
+    _is_terminal: bool,
+}
+
+/// The first bound that a config's states exceed, if any
+struct StatesShape(Option<ConfigError>);
+
+impl<'de> Deserialize<'de> for StatesShape {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StatesVisitor;
+
+        impl<'de> Visitor<'de> for StatesVisitor {
+            type Value = StatesShape;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a sequence of states")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<StatesShape, A::Error> {
+                let mut len = 0;
+                let mut error = None;
+                while let Some(state) = seq.next_element::<StateShape>()? {
+                    len += 1;
+                    if state.checks.0 > MAX_CHECKS_PER_STATE {
+                        error = error.or(Some(ConfigError::TooManyChecks));
+                    }
+                    if state.commands.0 > MAX_COMMANDS_PER_STATE {
+                        error = error.or(Some(ConfigError::TooManyCommands));
+                    }
+                }
+                if len > MAX_STATES {
+                    error = Some(ConfigError::TooManyStates);
+                }
+                Ok(StatesShape(error))
+            }
+        }
+
+        deserializer.deserialize_seq(StatesVisitor)
+    }
+}
+
+/// The length of a sequence of `T`, whose elements are decoded and dropped one at a time
+struct Count<T>(usize, PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Count<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CountVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for CountVisitor<T> {
+            type Value = Count<T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a sequence")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Count<T>, A::Error> {
+                let mut len = 0;
+                while seq.next_element::<T>()?.is_some() {
+                    len += 1;
+                }
+                Ok(Count(len, PhantomData))
+            }
+        }
+
+        deserializer.deserialize_seq(CountVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        crc32, load_config, load_config_page, load_config_page_or_else, store_config,
+        store_config_page, store_config_with_version, write_config_verified, FlashError, PageFlash,
+        StorageError, CONFIG_VERSION, PAGE_SIZE,
+    };
+    use crate::index::{
+        Check, Command, ConfigError, ConfigFile, ContinuityGuard, State, StateIndex,
+        StateTransition, Timeout,
+    };
+    use crate::{
+        AbortReason, CheckData, CommandObject, FloatCondition, Repeat, Seconds,
+        MAX_CHECKS_PER_STATE, MAX_COMMANDS_PER_STATE, MAX_STATES,
+    };
+    use core::fmt::Write;
+    use heapless::Vec;
+
+    /// A single flash page, which can flip a bit as it is programmed
+    struct MockFlash {
+        page: [u8; PAGE_SIZE],
+        flip_on_write: Option<usize>,
+    }
+
+    impl PageFlash for MockFlash {
+        type Error = ();
+
+        fn write_page(&mut self, page: &[u8; PAGE_SIZE]) -> Result<(), Self::Error> {
+            self.page = *page;
+            if let Some(offset) = self.flip_on_write {
+                self.page[offset] ^= 0x04;
+            }
+            Ok(())
+        }
+
+        fn read_page(&mut self, page: &mut [u8; PAGE_SIZE]) -> Result<(), Self::Error> {
+            *page = self.page;
+            Ok(())
+        }
+    }
+
+    fn test_config() -> ConfigFile {
+        // # SAFETY: Both states are pushed below
+        let (launch_idx, flight_idx) =
+            unsafe { (StateIndex::new_unchecked(0), StateIndex::new_unchecked(1)) };
+
+        let mut launch_checks = Vec::new();
+        launch_checks
+            .push(Check::new(
+                CheckData::Altitude(FloatCondition::GreaterThan(200.0)),
+                Some(StateTransition::Transition(flight_idx)),
+            ))
+            .unwrap();
+        let mut flight_commands = Vec::new();
+        let mut drogue = Command::new(CommandObject::Pyro1(true), Seconds(0.5));
+        drogue.pulse = Some(Seconds(1.0));
+        flight_commands.push(drogue).unwrap();
+
+        let mut states = Vec::new();
+        states
+            .push(State::new(
+                launch_checks,
+                Vec::new(),
+                Some(Timeout::new(10.0, StateTransition::Transition(flight_idx))),
+            ))
+            .unwrap();
+        states
+            .push(State::new(Vec::new(), flight_commands, None))
+            .unwrap();
+
+        ConfigFile {
+            default_state: launch_idx,
+            states,
+            continuity_guard: None,
+        }
+    }
+
+    /// Creates the largest config possible, with every state, check, and command slot filled
+    fn full_config() -> ConfigFile {
+        let mut states = Vec::new();
+
+        for i in 0..MAX_STATES {
+            // # SAFETY: Every state up to `MAX_STATES` is pushed
+            let next = unsafe { StateIndex::new_unchecked(((i + 1) % MAX_STATES) as u8) };
+            let safe = unsafe { StateIndex::new_unchecked(0) };
+
+            let mut checks = Vec::new();
+            for _ in 0..MAX_CHECKS_PER_STATE {
+                let mut check = Check::new(
+                    CheckData::Altitude(FloatCondition::between(100.0, 200.0)),
+                    Some(StateTransition::Abort(safe, AbortReason::SensorFault)),
+                );
+                check.group = Some(1);
+                check.priority = u8::MAX;
+                checks.push(check).unwrap();
+            }
+
+            let mut commands = Vec::new();
+            for _ in 0..MAX_COMMANDS_PER_STATE {
+                let mut command = Command::new(CommandObject::DataRate(u16::MAX), Seconds(1.5));
+                command.repeat = Repeat::Periodic;
+                command.pulse = Some(Seconds(1.0));
+                commands.push(command).unwrap();
+            }
+
+            let mut state = State::new(
+                checks,
+                commands,
+                Some(Timeout::new(30.0, StateTransition::Transition(next))),
+            );
+            state.fault_transition = Some(StateTransition::Abort(safe, AbortReason::SensorFault));
+            // The longest name allowed
+            let mut name = crate::StateName::new();
+            write!(name, "state {:>4}", i).unwrap();
+            state.name = Some(name);
+            states.push(state).unwrap();
+        }
+
+        ConfigFile {
+            // # SAFETY: There are `MAX_STATES` states
+            default_state: unsafe { StateIndex::new_unchecked(0) },
+            states,
+            continuity_guard: Some(ContinuityGuard {
+                timeout: Seconds(10.0),
+                // # SAFETY: There are `MAX_STATES` states
+                abort_to: unsafe { StateIndex::new_unchecked(MAX_STATES as u8 - 1) },
+            }),
+        }
+    }
+
+    #[test]
+    fn test_full_config_fits_page() {
+        let config = full_config();
+        let mut page = [0; PAGE_SIZE];
+
+        let len = store_config_page(&config, &mut page).unwrap();
+        assert!(len <= PAGE_SIZE);
+        assert!(page[len..].iter().all(|b| *b == 0xFF));
+        assert_eq!(load_config_page(&page), Ok(config));
+    }
+
+    #[test]
+    fn test_crc32() {
+        // The standard check value for CRC-32
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(&[]), 0);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let config = test_config();
+        let mut buf = [0; 512];
+
+        let len = store_config(&config, &mut buf).unwrap();
+        assert_eq!(load_config(&buf[..len]), Ok(config));
+    }
+
+    #[test]
+    fn test_corrupt_byte() {
+        let config = test_config();
+        let mut buf = [0; 512];
+
+        let len = store_config(&config, &mut buf).unwrap();
+        buf[len / 2] ^= 0x01;
+        assert!(matches!(
+            load_config(&buf[..len]),
+            Err(StorageError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unknown_version() {
+        let config = test_config();
+        let mut buf = [0; 512];
+
+        let len = store_config_with_version(&config, CONFIG_VERSION + 1, &mut buf).unwrap();
+        assert_eq!(
+            load_config(&buf[..len]),
+            Err(StorageError::UnsupportedVersion {
+                found: CONFIG_VERSION + 1,
+                expected: CONFIG_VERSION
+            })
+        );
+    }
+
+    #[test]
+    fn test_too_many_states() {
+        // Serializes the same as a `ConfigFile` with one more state than fits
+        let config = full_config();
+        let mut states: Vec<State, { MAX_STATES + 1 }> = config.states.iter().cloned().collect();
+        let mut buf = [0; 2 * PAGE_SIZE];
+
+        let fields = (config.default_state, &states, config.continuity_guard);
+        let len = store_config_with_version(&fields, CONFIG_VERSION, &mut buf).unwrap();
+        assert_eq!(load_config(&buf[..len]), Ok(config.clone()));
+
+        states.push(states[0].clone()).unwrap();
+        let too_many = (config.default_state, states, config.continuity_guard);
+        let len = store_config_with_version(&too_many, CONFIG_VERSION, &mut buf).unwrap();
+        assert_eq!(
+            load_config(&buf[..len]),
+            Err(StorageError::Config(ConfigError::TooManyStates))
+        );
+    }
+
+    /// Stores `config` with the checks and commands of its first state replaced by `checks` and
+    /// `commands`, which may be more than fit
+    fn store_with_first_state<const C: usize, const M: usize>(
+        config: &ConfigFile,
+        checks: Vec<Check, C>,
+        commands: Vec<Command, M>,
+        buf: &mut [u8],
+    ) -> usize {
+        let mut states: Vec<_, MAX_STATES> = Vec::new();
+        for (i, state) in config.states.iter().enumerate() {
+            let (checks, commands) = if i == 0 {
+                (checks.clone(), commands.clone())
+            } else {
+                let checks: Vec<Check, C> = state.checks.iter().cloned().collect();
+                (checks, state.commands.iter().cloned().collect())
+            };
+            // Serializes the same as a `State`
+            let fields = (
+                &state.name,
+                checks,
+                commands,
+                state.timeout,
+                state.fault_transition,
+                state.min_dwell,
+                state.is_terminal,
+            );
+            states.push(fields).unwrap();
+        }
+
+        let fields = (config.default_state, states, config.continuity_guard);
+        store_config_with_version(&fields, CONFIG_VERSION, buf).unwrap()
+    }
+
+    #[test]
+    fn test_too_many_checks() {
+        let config = full_config();
+        let first = &config.states[0];
+        let mut buf = [0; 2 * PAGE_SIZE];
+
+        // The same state stored this way loads back unchanged
+        let checks: Vec<Check, { MAX_CHECKS_PER_STATE + 1 }> =
+            first.checks.iter().cloned().collect();
+        let len = store_with_first_state(&config, checks.clone(), first.commands.clone(), &mut buf);
+        assert_eq!(load_config(&buf[..len]), Ok(config.clone()));
+
+        let mut checks = checks;
+        checks.push(first.checks[0].clone()).unwrap();
+        let len = store_with_first_state(&config, checks, first.commands.clone(), &mut buf);
+        assert_eq!(
+            load_config(&buf[..len]),
+            Err(StorageError::Config(ConfigError::TooManyChecks))
+        );
+    }
+
+    #[test]
+    fn test_too_many_commands() {
+        let config = full_config();
+        let first = &config.states[0];
+        let mut buf = [0; 2 * PAGE_SIZE];
+
+        let mut commands: Vec<Command, { MAX_COMMANDS_PER_STATE + 1 }> =
+            first.commands.iter().cloned().collect();
+        commands.push(first.commands[0]).unwrap();
+        let len = store_with_first_state(&config, first.checks.clone(), commands, &mut buf);
+        assert_eq!(
+            load_config(&buf[..len]),
+            Err(StorageError::Config(ConfigError::TooManyCommands))
+        );
+
+        // Corrupt bytes that happen to pass the CRC still don't decode as a config
+        let len = store_config_with_version(&(1u8, [0xFFu8; 8]), CONFIG_VERSION, &mut buf).unwrap();
+        assert_eq!(load_config(&buf[..len]), Err(StorageError::Deserialize));
+    }
+
+    #[test]
+    fn test_bad_magic() {
+        // Erased flash reads back as all ones
+        let buf = [0xFF; 512];
+        assert_eq!(load_config(&buf), Err(StorageError::BadMagic));
+    }
+
+    #[test]
+    fn test_buffer_too_small() {
+        let config = test_config();
+        let mut buf = [0; 16];
+
+        assert_eq!(
+            store_config(&config, &mut buf),
+            Err(StorageError::BufferTooSmall)
+        );
+
+        let mut buf = [0; 512];
+        let len = store_config(&config, &mut buf).unwrap();
+        assert_eq!(
+            load_config(&buf[..len - 1]),
+            Err(StorageError::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_page_fallback() {
+        let stored = test_config();
+        let fallback = full_config();
+
+        // A freshly erased page holds no config
+        let mut page = [0xFF; PAGE_SIZE];
+        let mut reason = None;
+        let config = load_config_page_or_else(&page, |e| {
+            reason = Some(e);
+            fallback.clone()
+        });
+        assert_eq!(config, fallback);
+        assert_eq!(reason, Some(StorageError::BadMagic));
+
+        store_config_page(&stored, &mut page).unwrap();
+        assert_eq!(
+            load_config_page_or_else(&page, |_| fallback.clone()),
+            stored
+        );
+
+        page[20] ^= 0x80;
+        let config = load_config_page_or_else(&page, |e| {
+            assert!(matches!(e, StorageError::ChecksumMismatch { .. }));
+            fallback.clone()
+        });
+        assert_eq!(config, fallback);
+    }
+
+    #[test]
+    fn test_write_config_verified() {
+        let config = test_config();
+        let mut page = [0; PAGE_SIZE];
+        let mut readback = [0; PAGE_SIZE];
+
+        let mut flash = MockFlash {
+            page: [0xFF; PAGE_SIZE],
+            flip_on_write: None,
+        };
+        let len = write_config_verified(&mut flash, &config, &mut page, &mut readback).unwrap();
+        assert_eq!(load_config(&flash.page[..len]), Ok(config.clone()));
+
+        flash.flip_on_write = Some(10);
+        assert_eq!(
+            write_config_verified(&mut flash, &config, &mut page, &mut readback),
+            Err(FlashError::Storage(StorageError::VerifyMismatch {
+                offset: 10
+            }))
+        );
+    }
+}
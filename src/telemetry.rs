@@ -0,0 +1,364 @@
+//! A fixed-size telemetry packet, streamed to the ground over the radio during flight.
+//!
+//! A packet is laid out as:
+//!
+//! | Bytes | Contents                                                  |
+//! |-------|-----------------------------------------------------------|
+//! | 1     | The id of the current state                               |
+//! | 1     | [`TelemetryFlags`], packed as a bitfield                  |
+//! | 4     | Milliseconds since boot, little endian                    |
+//! | 4     | Altitude in meters, as an `f32`                           |
+//! | 4     | Velocity in meters per second, as an `f32`                |
+//! | 2     | Battery voltage in millivolts                             |
+//! | 4     | CRC-32 of all of the preceding bytes                      |
+//!
+//! All multi-byte values are little endian. At [`PACKET_SIZE`] bytes a packet fits in a single
+//! LoRa payload with plenty of room to spare.
+//!
+//! On a byte stream, packets are sent as frames, see [`encode_frame`]. Each frame is the packet
+//! byte-stuffed with COBS so it contains no zero bytes, followed by a zero byte which marks the
+//! end of the frame. A receiver that picks up noise can always resync at the next zero byte.
+
+use heapless::Vec;
+
+use crate::storage::crc32;
+
+/// The size of an encoded packet
+pub const PACKET_SIZE: usize = 20;
+
+/// The size of a framed packet, which is the packet with COBS's one byte of overhead and the zero
+/// byte ending the frame
+pub const FRAME_SIZE: usize = PACKET_SIZE + 2;
+
+const CRC_OFFSET: usize = PACKET_SIZE - 4;
+
+/// An error encountered while decoding a telemetry packet
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TelemetryError {
+    /// The buffer is too small to hold a packet
+    BufferTooSmall,
+    /// The stored CRC does not match the received bytes, so the packet was corrupted in transit
+    ChecksumMismatch { stored: u32, computed: u32 },
+    /// A frame was not valid COBS or was the wrong length for a packet, such as one which started
+    /// partway through or lost bytes in transit
+    InvalidFrame,
+}
+
+/// Status flags sent in each packet
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct TelemetryFlags {
+    pub armed: bool,
+    pub apogee: bool,
+    pub burnout: bool,
+    pub landed: bool,
+    /// Whether each pyro channel, from 1 to 3, has continuity
+    pub continuity: [bool; 3],
+}
+
+impl TelemetryFlags {
+    /// Packs the flags into a byte, one bit each from the least significant bit in field order
+    pub fn to_byte(self) -> u8 {
+        let [pyro1, pyro2, pyro3] = self.continuity;
+        [
+            self.armed,
+            self.apogee,
+            self.burnout,
+            self.landed,
+            pyro1,
+            pyro2,
+            pyro3,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |byte, (bit, set)| byte | (u8::from(*set) << bit))
+    }
+
+    /// Unpacks the flags from a byte, ignoring unused bits
+    pub fn from_byte(byte: u8) -> Self {
+        let bit = |n: u8| byte & (1 << n) != 0;
+        Self {
+            armed: bit(0),
+            apogee: bit(1),
+            burnout: bit(2),
+            landed: bit(3),
+            continuity: [bit(4), bit(5), bit(6)],
+        }
+    }
+}
+
+/// A snapshot of the flight computer, sent to the ground over the radio
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TelemetryPacket {
+    /// The id of the current state
+    pub state_id: u8,
+    pub flags: TelemetryFlags,
+    /// Milliseconds since boot
+    pub timestamp_ms: u32,
+    /// Meters above the ground
+    pub altitude: f32,
+    /// Meters per second, up positive
+    pub velocity: f32,
+    /// Battery voltage in millivolts
+    pub battery_mv: u16,
+}
+
+impl TelemetryPacket {
+    /// Encodes this packet, including its CRC
+    pub fn encode(&self) -> [u8; PACKET_SIZE] {
+        let mut buf = [0; PACKET_SIZE];
+        buf[0] = self.state_id;
+        buf[1] = self.flags.to_byte();
+        buf[2..6].copy_from_slice(&self.timestamp_ms.to_le_bytes());
+        buf[6..10].copy_from_slice(&self.altitude.to_le_bytes());
+        buf[10..14].copy_from_slice(&self.velocity.to_le_bytes());
+        buf[14..16].copy_from_slice(&self.battery_mv.to_le_bytes());
+
+        let crc = crc32(&buf[..CRC_OFFSET]);
+        buf[CRC_OFFSET..].copy_from_slice(&crc.to_le_bytes());
+        buf
+    }
+
+    /// Decodes the packet at the start of `buf`, verifying its CRC
+    pub fn decode(buf: &[u8]) -> Result<Self, TelemetryError> {
+        let buf = buf
+            .get(..PACKET_SIZE)
+            .ok_or(TelemetryError::BufferTooSmall)?;
+
+        let stored = u32::from_le_bytes([buf[16], buf[17], buf[18], buf[19]]);
+        let computed = crc32(&buf[..CRC_OFFSET]);
+        if stored != computed {
+            return Err(TelemetryError::ChecksumMismatch { stored, computed });
+        }
+
+        Ok(Self {
+            state_id: buf[0],
+            flags: TelemetryFlags::from_byte(buf[1]),
+            timestamp_ms: u32::from_le_bytes([buf[2], buf[3], buf[4], buf[5]]),
+            altitude: f32::from_le_bytes([buf[6], buf[7], buf[8], buf[9]]),
+            velocity: f32::from_le_bytes([buf[10], buf[11], buf[12], buf[13]]),
+            battery_mv: u16::from_le_bytes([buf[14], buf[15]]),
+        })
+    }
+}
+
+/// Encodes `packet` as a frame, ready to be written to the radio
+pub fn encode_frame(packet: &TelemetryPacket) -> [u8; FRAME_SIZE] {
+    let packet = packet.encode();
+    let mut frame = [0; FRAME_SIZE];
+
+    // Each zero is replaced by the distance to the next one, with the first distance stored in a
+    // leading byte and an implied zero after the end of the packet. A packet is less than 254
+    // bytes, so no block is ever long enough to need splitting
+    let mut code_index = 0;
+    let mut code = 1;
+    for (i, byte) in packet.iter().enumerate() {
+        if *byte == 0 {
+            frame[code_index] = code;
+            code_index = i + 1;
+            code = 1;
+        } else {
+            frame[i + 1] = *byte;
+            code += 1;
+        }
+    }
+    frame[code_index] = code;
+
+    frame
+}
+
+/// Undoes the COBS encoding of `frame`, which does not include the zero byte ending it
+fn decode_cobs(frame: &[u8]) -> Result<Vec<u8, PACKET_SIZE>, TelemetryError> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < frame.len() {
+        let code = frame[i] as usize;
+        let block = frame
+            .get(i + 1..i + code)
+            .ok_or(TelemetryError::InvalidFrame)?;
+        if code == 0 || block.contains(&0) {
+            return Err(TelemetryError::InvalidFrame);
+        }
+        out.extend_from_slice(block)
+            .map_err(|_| TelemetryError::InvalidFrame)?;
+
+        i += code;
+        // The zero implied after the last block is not part of the packet
+        if i < frame.len() {
+            out.push(0).map_err(|_| TelemetryError::InvalidFrame)?;
+        }
+    }
+    Ok(out)
+}
+
+/// Collects received bytes into frames, yielding verified packets from a noisy byte stream
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    frame: Vec<u8, { FRAME_SIZE - 1 }>,
+    overflowed: bool,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self {
+            frame: Vec::new(),
+            overflowed: false,
+        }
+    }
+
+    /// Adds a received byte, returning the decoded packet once a full frame has been received.
+    ///
+    /// A frame that was corrupted returns an error, after which decoding continues with the next
+    /// frame.
+    pub fn push(&mut self, byte: u8) -> Option<Result<TelemetryPacket, TelemetryError>> {
+        if byte != 0 {
+            if self.frame.push(byte).is_err() {
+                self.overflowed = true;
+            }
+            return None;
+        }
+
+        // Back to back zeros, such as padding between frames, are ignored
+        if self.frame.is_empty() && !self.overflowed {
+            return None;
+        }
+
+        let result = if self.overflowed {
+            Err(TelemetryError::InvalidFrame)
+        } else {
+            decode_cobs(&self.frame).and_then(|packet| {
+                if packet.len() == PACKET_SIZE {
+                    TelemetryPacket::decode(&packet)
+                } else {
+                    Err(TelemetryError::InvalidFrame)
+                }
+            })
+        };
+        self.frame.clear();
+        self.overflowed = false;
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        encode_frame, FrameDecoder, TelemetryError, TelemetryFlags, TelemetryPacket, FRAME_SIZE,
+        PACKET_SIZE,
+    };
+
+    fn packet() -> TelemetryPacket {
+        TelemetryPacket {
+            state_id: 3,
+            flags: TelemetryFlags {
+                armed: true,
+                apogee: true,
+                burnout: true,
+                landed: false,
+                continuity: [true, false, true],
+            },
+            timestamp_ms: 12_345,
+            altitude: 1523.25,
+            velocity: -4.5,
+            battery_mv: 7_900,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let packet = packet();
+        let buf = packet.encode();
+        assert_eq!(TelemetryPacket::decode(&buf), Ok(packet));
+        assert_eq!(buf[1], 0b0101_0111);
+
+        let empty = TelemetryPacket {
+            flags: TelemetryFlags::default(),
+            ..packet
+        };
+        assert_eq!(TelemetryPacket::decode(&empty.encode()), Ok(empty));
+
+        assert_eq!(
+            TelemetryPacket::decode(&buf[..PACKET_SIZE - 1]),
+            Err(TelemetryError::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_corrupted_packet() {
+        let mut buf = packet().encode();
+        buf[7] ^= 0x10;
+        assert!(matches!(
+            TelemetryPacket::decode(&buf),
+            Err(TelemetryError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_frame() {
+        // Zeros throughout, including at the start and end of the packet
+        let zeros = TelemetryPacket {
+            state_id: 0,
+            flags: TelemetryFlags::default(),
+            timestamp_ms: 0x0100,
+            altitude: 0.0,
+            velocity: 2.0,
+            battery_mv: 0,
+        };
+        for packet in [zeros, packet()] {
+            let frame = encode_frame(&packet);
+            assert_eq!(frame[FRAME_SIZE - 1], 0);
+            assert!(!frame[..FRAME_SIZE - 1].contains(&0));
+
+            let mut decoder = FrameDecoder::new();
+            let (last, rest) = frame.split_last().unwrap();
+            assert!(rest.iter().all(|byte| decoder.push(*byte).is_none()));
+            assert_eq!(decoder.push(*last), Some(Ok(packet)));
+        }
+    }
+
+    #[test]
+    fn test_frame_recovery() {
+        let packets = [3, 4, 5, 6].map(|state_id| TelemetryPacket {
+            state_id,
+            ..packet()
+        });
+
+        let mut stream = heapless::Vec::<u8, 256>::new();
+        let mut send = |bytes: &[u8]| stream.extend_from_slice(bytes).unwrap();
+        // The receiver started listening partway through a frame
+        send(&encode_frame(&packets[0])[7..]);
+        send(&encode_frame(&packets[0]));
+        // Noise ending in a zero byte is dropped as its own bad frame
+        send(&[0x13, 0x37, 0x00, 0x00]);
+        send(&encode_frame(&packets[1]));
+        // Noise without a zero byte corrupts the frame after it
+        send(&[0xAB; 40]);
+        send(&encode_frame(&packets[2]));
+        // A byte flipped in transit fails the CRC
+        let mut corrupted = encode_frame(&packets[2]);
+        corrupted[9] ^= 0x01;
+        send(&corrupted);
+        send(&encode_frame(&packets[3]));
+
+        let mut decoder = FrameDecoder::new();
+        let results: heapless::Vec<_, 8> = stream
+            .iter()
+            .filter_map(|byte| decoder.push(*byte))
+            .collect();
+        assert_eq!(results.len(), 7);
+        assert_eq!(
+            results[..5],
+            [
+                Err(TelemetryError::InvalidFrame),
+                Ok(packets[0]),
+                Err(TelemetryError::InvalidFrame),
+                Ok(packets[1]),
+                Err(TelemetryError::InvalidFrame),
+            ]
+        );
+        assert!(matches!(
+            results[5],
+            Err(TelemetryError::ChecksumMismatch { .. })
+        ));
+        assert_eq!(results[6], Ok(packets[3]));
+    }
+}
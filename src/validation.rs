@@ -0,0 +1,255 @@
+//! Checks for mistakes in a config that would still convert and run, but are almost certainly
+//! not what was intended. This works on the [`crate::index`] representation, so it can be run on
+//! the host before a config is uploaded.
+
+use crate::index::{ConfigFile, StateIndex};
+use crate::{CommandObject, Seconds, MAX_COMMANDS_PER_STATE, MAX_STATES};
+
+use heapless::Vec;
+
+/// The problems found in a config by [`validate`]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Report {
+    /// States that cannot be reached from the default state by any transition
+    pub unreachable_states: Vec<StateIndex, MAX_STATES>,
+    /// States that are not marked terminal, but have no check transitions and no timeout, so they
+    /// can never be left
+    pub dead_end_states: Vec<StateIndex, MAX_STATES>,
+    /// `DataRate` commands, by state and rate in Hz, that sample outside of
+    /// [`crate::MIN_DATA_RATE_HZ`] to [`crate::MAX_DATA_RATE_HZ`]
+    pub unsupported_data_rates: Vec<(StateIndex, u16), { MAX_STATES * MAX_COMMANDS_PER_STATE }>,
+    /// Periodic commands, by state and interval, whose interval is not a positive number of seconds
+    pub invalid_intervals: Vec<(StateIndex, Seconds), { MAX_STATES * MAX_COMMANDS_PER_STATE }>,
+}
+
+impl Report {
+    /// Returns true if no problems were found
+    pub fn is_ok(&self) -> bool {
+        self.unreachable_states.is_empty()
+            && self.dead_end_states.is_empty()
+            && self.unsupported_data_rates.is_empty()
+            && self.invalid_intervals.is_empty()
+    }
+}
+
+/// Validates `config`, returning every problem found
+pub fn validate(config: &ConfigFile) -> Report {
+    let mut report = Report::default();
+
+    let reachable = reachable_states(config);
+    for (i, state) in config.states.iter().enumerate() {
+        // # SAFETY: `i` is the index of one of `config`'s states
+        let index = unsafe { StateIndex::new_unchecked(i as u8) };
+
+        // Neither push can overflow, as there are at most `MAX_STATES` states
+        if !reachable[i] {
+            let _ = report.unreachable_states.push(index);
+        }
+
+        let has_exit =
+            state.timeout.is_some() || state.checks.iter().any(|check| check.transition.is_some());
+        if !has_exit && !state.is_terminal {
+            let _ = report.dead_end_states.push(index);
+        }
+
+        for command in state.commands.iter() {
+            if let CommandObject::DataRate(rate) = command.object {
+                if !command.object.is_supported() {
+                    // There is room for every command in every state
+                    let _ = report.unsupported_data_rates.push((index, rate));
+                }
+            }
+            if !command.has_valid_interval() {
+                // There is room for every command in every state
+                let _ = report.invalid_intervals.push((index, command.delay));
+            }
+        }
+    }
+
+    report
+}
+
+/// Walks every transition starting from the default state, returning which states were reached
+fn reachable_states(config: &ConfigFile) -> [bool; MAX_STATES] {
+    let mut reached = [false; MAX_STATES];
+    let mut to_visit: Vec<StateIndex, MAX_STATES> = Vec::new();
+
+    let mut visit = |index: StateIndex, to_visit: &mut Vec<StateIndex, MAX_STATES>| {
+        // Out of range indices are reported by `indices_to_refs`, so they are skipped here
+        let i = usize::from(index);
+        if i < config.states.len() && !reached[i] {
+            reached[i] = true;
+            // Each state is only pushed once, so this cannot overflow
+            let _ = to_visit.push(index);
+        }
+    };
+
+    visit(config.default_state, &mut to_visit);
+    while let Some(index) = to_visit.pop() {
+        let state = &config.states[usize::from(index)];
+        for transition in state.transitions() {
+            visit(transition.state(), &mut to_visit);
+        }
+        // A pyro command that never sees continuity aborts to the guard's state
+        if let Some(guard) = &config.continuity_guard {
+            if state
+                .commands
+                .iter()
+                .any(|command| command.object.continuity_check().is_some())
+            {
+                visit(guard.abort_to, &mut to_visit);
+            }
+        }
+    }
+
+    reached
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate;
+    use crate::index::{Check, Command, ConfigFile, State, StateIndex, StateTransition, Timeout};
+    use crate::{
+        CheckData, CommandObject, FloatCondition, Repeat, Seconds, MAX_DATA_RATE_HZ,
+        MIN_DATA_RATE_HZ,
+    };
+    use heapless::Vec;
+
+    fn index(i: u8) -> StateIndex {
+        // # SAFETY: Every index used in these tests is in bounds
+        unsafe { StateIndex::new_unchecked(i) }
+    }
+
+    #[test]
+    fn test_unreachable_state() {
+        let mut states = Vec::new();
+
+        // 0: Launch, which times out into Flight
+        let launch = State::new(
+            Vec::new(),
+            Vec::new(),
+            Some(Timeout::new(1.0, StateTransition::Transition(index(1)))),
+        );
+        states.push(launch).unwrap();
+
+        // 1: Flight, which transitions to Descent
+        let mut flight_checks = Vec::new();
+        flight_checks
+            .push(Check::new(
+                CheckData::Altitude(FloatCondition::LessThan(100.0)),
+                Some(StateTransition::Transition(index(2))),
+            ))
+            .unwrap();
+        states
+            .push(State::new(flight_checks, Vec::new(), None))
+            .unwrap();
+
+        // 2: Descent
+        states
+            .push(State::new(Vec::new(), Vec::new(), None))
+            .unwrap();
+
+        // 3: Orphaned, nothing transitions here
+        states
+            .push(State::new(Vec::new(), Vec::new(), None))
+            .unwrap();
+
+        let config = ConfigFile {
+            default_state: index(0),
+            states,
+            continuity_guard: None,
+        };
+
+        let report = validate(&config);
+        assert!(!report.is_ok());
+        assert_eq!(report.unreachable_states.as_slice(), &[index(3)]);
+    }
+
+    /// Creates a config where Flight transitions to Landed, which has no way out
+    fn flight_to_landed(landed_is_terminal: bool) -> ConfigFile {
+        let mut states = Vec::new();
+
+        let mut flight_checks = Vec::new();
+        flight_checks
+            .push(Check::new(
+                CheckData::Altitude(FloatCondition::LessThan(5.0)),
+                Some(StateTransition::Transition(index(1))),
+            ))
+            .unwrap();
+        states
+            .push(State::new(flight_checks, Vec::new(), None))
+            .unwrap();
+
+        let mut landed = State::new(Vec::new(), Vec::new(), None);
+        landed.is_terminal = landed_is_terminal;
+        states.push(landed).unwrap();
+
+        ConfigFile {
+            default_state: index(0),
+            states,
+            continuity_guard: None,
+        }
+    }
+
+    #[test]
+    fn test_terminal_state() {
+        let report = validate(&flight_to_landed(true));
+        assert!(report.is_ok());
+    }
+
+    #[test]
+    fn test_dead_end_state() {
+        let report = validate(&flight_to_landed(false));
+        assert!(!report.is_ok());
+        assert_eq!(report.dead_end_states.as_slice(), &[index(1)]);
+    }
+
+    #[test]
+    fn test_unsupported_data_rate() {
+        let data_rate = |rate| Command::new(CommandObject::DataRate(rate), Seconds(0.0));
+
+        let mut config = flight_to_landed(true);
+        config.states[0]
+            .commands
+            .push(data_rate(MAX_DATA_RATE_HZ + 1))
+            .unwrap();
+        for rate in [0, MIN_DATA_RATE_HZ - 1, MIN_DATA_RATE_HZ] {
+            config.states[1].commands.push(data_rate(rate)).unwrap();
+        }
+
+        let report = validate(&config);
+        assert!(!report.is_ok());
+        assert_eq!(
+            report.unsupported_data_rates.as_slice(),
+            &[
+                (index(0), MAX_DATA_RATE_HZ + 1),
+                (index(1), 0),
+                (index(1), MIN_DATA_RATE_HZ - 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_interval() {
+        let periodic = |interval| {
+            let mut command = Command::new(CommandObject::Beacon(true), Seconds(interval));
+            command.repeat = Repeat::Periodic;
+            command
+        };
+
+        let mut config = flight_to_landed(true);
+        config.states[0].commands.push(periodic(0.0)).unwrap();
+        config.states[0].commands.push(periodic(1.0)).unwrap();
+        config.states[1].commands.push(periodic(-2.0)).unwrap();
+        // Only periodic commands have an interval
+        let once = Command::new(CommandObject::Beacon(true), Seconds(0.0));
+        config.states[1].commands.push(once).unwrap();
+
+        let report = validate(&config);
+        assert!(!report.is_ok());
+        assert_eq!(
+            report.invalid_intervals.as_slice(),
+            &[(index(0), Seconds(0.0)), (index(1), Seconds(-2.0))]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! The latest sensor-derived values that checks are evaluated against. Data acquisition writes
+//! each new [`Reading`] in, and the state machine reads them back by [`CheckKind`].
+
+use crate::conversions::pressure_to_altitude;
+use crate::detection::descent_rate;
+use crate::filter::GroundCalibration;
+use crate::{CheckKind, ObjectState};
+
+/// Something checks can read their values from, such as a [`DataWorkspace`]
+pub trait Workspace {
+    /// Returns the current value of `kind`, or `None` if it has no value yet.
+    ///
+    /// The value is always `ObjectState::Float` for a kind checked by a [`crate::FloatCondition`]
+    /// and `ObjectState::Flag` for any other kind, so [`crate::CheckData::is_satisfied`] never
+    /// sees a mismatched variant
+    fn get_object(&self, kind: CheckKind) -> Option<ObjectState>;
+}
+
+/// A new value for one of the objects stored in a [`DataWorkspace`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Reading {
+    /// Meters above the ground
+    Altitude(f32),
+    /// Barometric pressure in pascals, which is stored as an altitude above the calibrated ground,
+    /// see [`DataWorkspace::calibrate_ground`]
+    Pressure(f32),
+    /// Meters per second, positive up
+    Velocity(f32),
+    /// Meters per second squared, positive up
+    Acceleration(f32),
+    /// Degrees from vertical
+    Tilt(f32),
+    ApogeeFlag(bool),
+    Pyro1Continuity(bool),
+    Pyro2Continuity(bool),
+    Pyro3Continuity(bool),
+    LandedFlag(bool),
+    BurnoutFlag(bool),
+    MachLockFlag(bool),
+    /// Volts
+    BatteryVoltage(f32),
+    /// Seconds since boot
+    TimeSinceBoot(f32),
+    /// Seconds since boot at which liftoff was detected
+    LiftoffTime(f32),
+}
+
+/// Stores the latest value of every object a check can read.
+///
+/// Values are stored by type rather than as [`ObjectState`]s, and [`Workspace::get_object`] builds
+/// the matching variant for each kind. Numeric values have no value until they are first read,
+/// while flags start out clear
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct DataWorkspace {
+    altitude: Option<f32>,
+    velocity: Option<f32>,
+    acceleration: Option<f32>,
+    tilt: Option<f32>,
+    battery_voltage: Option<f32>,
+    time_since_boot: Option<f32>,
+    liftoff_time: Option<f32>,
+    apogee: bool,
+    landed: bool,
+    burnout: bool,
+    mach_lock: bool,
+    /// Whether each pyro channel, from 1 to 3, has continuity
+    continuity: [bool; 3],
+    calibration: Option<GroundCalibration>,
+}
+
+impl DataWorkspace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns this workspace with `reading` applied, see [`Self::update`]
+    pub fn with(mut self, reading: Reading) -> Self {
+        self.update(reading);
+        self
+    }
+
+    /// Starts averaging the next `samples` pressure readings into the ground reference. This
+    /// should be called on the pad before arming. Until it completes, altitudes from
+    /// [`Reading::Pressure`] are relative to sea level, and afterwards they are above the ground
+    pub fn calibrate_ground(&mut self, samples: u16) {
+        self.calibration = Some(GroundCalibration::new(samples));
+    }
+
+    /// Returns the calibrated ground pressure in pascals, or `None` while still calibrating
+    pub fn ground_pressure(&self) -> Option<f32> {
+        self.calibration
+            .and_then(|calibration| calibration.ground_pressure())
+    }
+
+    /// Replaces the stored value that `reading` is a new value of
+    pub fn update(&mut self, reading: Reading) {
+        match reading {
+            Reading::Altitude(value) => self.altitude = Some(value),
+            Reading::Pressure(value) => {
+                if let Some(calibration) = &mut self.calibration {
+                    calibration.update(value);
+                }
+                self.altitude = Some(pressure_to_altitude(value, self.ground_pressure()));
+            }
+            Reading::Velocity(value) => self.velocity = Some(value),
+            Reading::Acceleration(value) => self.acceleration = Some(value),
+            Reading::Tilt(value) => self.tilt = Some(value),
+            Reading::ApogeeFlag(value) => self.apogee = value,
+            Reading::Pyro1Continuity(value) => self.continuity[0] = value,
+            Reading::Pyro2Continuity(value) => self.continuity[1] = value,
+            Reading::Pyro3Continuity(value) => self.continuity[2] = value,
+            Reading::LandedFlag(value) => self.landed = value,
+            Reading::BurnoutFlag(value) => self.burnout = value,
+            Reading::MachLockFlag(value) => self.mach_lock = value,
+            Reading::BatteryVoltage(value) => self.battery_voltage = Some(value),
+            Reading::TimeSinceBoot(value) => self.time_since_boot = Some(value),
+            Reading::LiftoffTime(value) => self.liftoff_time = Some(value),
+        }
+    }
+}
+
+impl Workspace for DataWorkspace {
+    fn get_object(&self, kind: CheckKind) -> Option<ObjectState> {
+        match kind {
+            CheckKind::Altitude => self.altitude.map(ObjectState::Float),
+            CheckKind::Velocity => self.velocity.map(ObjectState::Float),
+            CheckKind::Acceleration => self.acceleration.map(ObjectState::Float),
+            CheckKind::Tilt => self.tilt.map(ObjectState::Float),
+            CheckKind::ApogeeFlag => Some(ObjectState::Flag(self.apogee)),
+            CheckKind::Pyro1Continuity => Some(ObjectState::Flag(self.continuity[0])),
+            CheckKind::Pyro2Continuity => Some(ObjectState::Flag(self.continuity[1])),
+            CheckKind::Pyro3Continuity => Some(ObjectState::Flag(self.continuity[2])),
+            CheckKind::LandedFlag => Some(ObjectState::Flag(self.landed)),
+            CheckKind::BurnoutFlag => Some(ObjectState::Flag(self.burnout)),
+            CheckKind::MachLockFlag => Some(ObjectState::Flag(self.mach_lock)),
+            CheckKind::DescentRate => self
+                .velocity
+                .map(|velocity| ObjectState::Float(descent_rate(velocity))),
+            CheckKind::BatteryVoltage => self.battery_voltage.map(ObjectState::Float),
+            CheckKind::TimeSinceBoot => self.time_since_boot.map(ObjectState::Float),
+            CheckKind::TimeSinceLiftoff => Some(ObjectState::Float(
+                self.time_since_boot? - self.liftoff_time?,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DataWorkspace, Reading, Workspace};
+    use crate::{CheckData, CheckKind, FloatCondition, NativeFlagCondition, ObjectState};
+
+    #[test]
+    fn test_altitude_read_back() {
+        let mut workspace = DataWorkspace::new();
+        assert_eq!(workspace.get_object(CheckKind::Altitude), None);
+
+        workspace.update(Reading::Altitude(250.0));
+        assert_eq!(
+            workspace.get_object(CheckKind::Altitude),
+            Some(ObjectState::Float(250.0))
+        );
+        assert!(CheckData::Altitude(FloatCondition::LessThan(300.0))
+            .is_satisfied(workspace.get_object(CheckKind::Altitude).unwrap()));
+    }
+
+    #[test]
+    fn test_variant_matches_kind() {
+        let workspace = DataWorkspace::new()
+            .with(Reading::Altitude(120.0))
+            .with(Reading::Velocity(-8.0))
+            .with(Reading::Acceleration(-9.8))
+            .with(Reading::Tilt(4.0))
+            .with(Reading::ApogeeFlag(true))
+            .with(Reading::Pyro2Continuity(true))
+            .with(Reading::BatteryVoltage(7.9))
+            .with(Reading::TimeSinceBoot(30.0))
+            .with(Reading::LiftoffTime(12.5));
+
+        let floats = [
+            (CheckKind::Altitude, 120.0),
+            (CheckKind::Velocity, -8.0),
+            (CheckKind::Acceleration, -9.8),
+            (CheckKind::Tilt, 4.0),
+            (CheckKind::DescentRate, 8.0),
+            (CheckKind::BatteryVoltage, 7.9),
+            (CheckKind::TimeSinceBoot, 30.0),
+            (CheckKind::TimeSinceLiftoff, 17.5),
+        ];
+        for (kind, value) in floats {
+            assert_eq!(workspace.get_object(kind), Some(ObjectState::Float(value)));
+        }
+
+        let flags = [
+            (CheckKind::ApogeeFlag, true),
+            (CheckKind::Pyro1Continuity, false),
+            (CheckKind::Pyro2Continuity, true),
+            (CheckKind::Pyro3Continuity, false),
+            (CheckKind::LandedFlag, false),
+            (CheckKind::BurnoutFlag, false),
+            (CheckKind::MachLockFlag, false),
+        ];
+        for (kind, value) in flags {
+            assert_eq!(workspace.get_object(kind), Some(ObjectState::Flag(value)));
+        }
+        assert!(CheckData::ApogeeFlag(NativeFlagCondition(true))
+            .is_satisfied(workspace.get_object(CheckKind::ApogeeFlag).unwrap()));
+
+        // There is no time since liftoff until liftoff has been detected
+        let on_pad = DataWorkspace::new().with(Reading::TimeSinceBoot(3.0));
+        assert_eq!(on_pad.get_object(CheckKind::TimeSinceLiftoff), None);
+    }
+
+    #[test]
+    fn test_ground_calibration() {
+        let mut workspace = DataWorkspace::new();
+        workspace.calibrate_ground(3);
+
+        for pressure in [97_010.0, 96_990.0, 97_000.0] {
+            workspace.update(Reading::Pressure(pressure));
+        }
+        let ground = workspace.ground_pressure().unwrap();
+        assert!((ground - 97_000.0).abs() < 0.1);
+
+        // Sitting on the pad at the reference pressure reads as 0 AGL
+        workspace.update(Reading::Pressure(97_000.0));
+        match workspace.get_object(CheckKind::Altitude) {
+            Some(ObjectState::Float(altitude)) => assert!(altitude.abs() < 0.1, "{}", altitude),
+            other => panic!("{:?}", other),
+        }
+    }
+}
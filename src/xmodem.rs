@@ -0,0 +1,342 @@
+//! The sending side of XMODEM, used to download the flight log over serial with checksums and
+//! retransmission.
+//!
+//! This only implements the protocol, so the firmware feeds [`Sender`] each byte received from
+//! the ground and each timeout, then sends whatever it returns. Blocks are read from the flash
+//! log by their index, so the whole log never has to be held in memory.
+//!
+//! Both the original checksum and the CRC variant of XMODEM are supported, and the receiver picks
+//! between them when it starts the transfer.
+
+/// The number of bytes of data in each block
+pub const BLOCK_SIZE: usize = 128;
+
+/// The size of the largest encoded block, which is a block in CRC mode
+pub const MAX_PACKET_SIZE: usize = 3 + BLOCK_SIZE + 2;
+
+/// The most times a block is sent before the transfer gives up
+pub const MAX_RETRIES: u8 = 10;
+
+/// Starts each block
+pub const SOH: u8 = 0x01;
+/// Sent for [`Action::SendEot`]
+pub const EOT: u8 = 0x04;
+pub const ACK: u8 = 0x06;
+pub const NAK: u8 = 0x15;
+/// Sent for [`Action::Cancel`], or by the receiver to cancel the transfer
+pub const CAN: u8 = 0x18;
+/// Sent by a receiver in place of [`NAK`] to start a transfer in CRC mode
+pub const CRC_START: u8 = b'C';
+/// Pads the last block
+const PADDING: u8 = 0x1A;
+
+/// An error that ended a transfer
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum XmodemError {
+    /// A block or the end of the transfer was not acknowledged after [`MAX_RETRIES`] attempts
+    TooManyRetries,
+    /// The receiver cancelled the transfer
+    Cancelled,
+}
+
+/// Something that happened on the serial link
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A byte was received from the ground
+    Received(u8),
+    /// Nothing was received within the timeout after the last thing was sent
+    Timeout,
+}
+
+/// What the caller should do next
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Nothing needs to be sent, so keep waiting for the next event
+    Wait,
+    /// Encode the block at this index with [`Sender::encode_block`] and send it
+    SendBlock(u32),
+    /// Send the byte marking the end of the transfer
+    SendEot,
+    /// Send the byte cancelling the transfer, after which it has failed with this error
+    Cancel(XmodemError),
+    /// The receiver has acknowledged everything, so the transfer is complete
+    Finished,
+}
+
+/// How each block is checked, which the receiver picks
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// A one byte sum of the data
+    Checksum,
+    /// A CRC-16 of the data
+    Crc,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum SenderState {
+    WaitingForStart,
+    SentBlock(u32),
+    SentEot,
+    Done,
+}
+
+/// The state of a transfer from the flight computer to the ground
+#[derive(Debug, Clone)]
+pub struct Sender {
+    state: SenderState,
+    mode: Mode,
+    block_count: u32,
+    retries: u8,
+}
+
+impl Sender {
+    /// Creates a sender for `len` bytes of data, which waits for the receiver to start the
+    /// transfer
+    pub fn new(len: usize) -> Self {
+        Self {
+            state: SenderState::WaitingForStart,
+            mode: Mode::Crc,
+            block_count: len.div_ceil(BLOCK_SIZE) as u32,
+            retries: 0,
+        }
+    }
+
+    /// Returns the mode the receiver picked, which is only meaningful once blocks are being sent
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Returns the number of blocks the data is sent in
+    pub fn block_count(&self) -> u32 {
+        self.block_count
+    }
+
+    /// Advances the transfer after `event`, returning what should be sent next
+    pub fn handle(&mut self, event: Event) -> Action {
+        if event == Event::Received(CAN) && self.state != SenderState::Done {
+            self.state = SenderState::Done;
+            return Action::Cancel(XmodemError::Cancelled);
+        }
+
+        match (self.state, event) {
+            (SenderState::WaitingForStart, Event::Received(byte @ (NAK | CRC_START))) => {
+                self.mode = if byte == NAK {
+                    Mode::Checksum
+                } else {
+                    Mode::Crc
+                };
+                self.next_block(0)
+            }
+            // The receiver may take a while to start, and sends its start byte repeatedly
+            (SenderState::WaitingForStart, _) => Action::Wait,
+
+            (SenderState::SentBlock(block), Event::Received(ACK)) => self.next_block(block + 1),
+            (SenderState::SentBlock(block), Event::Received(NAK) | Event::Timeout) => {
+                self.retry(Action::SendBlock(block))
+            }
+
+            (SenderState::SentEot, Event::Received(ACK)) => {
+                self.state = SenderState::Done;
+                Action::Finished
+            }
+            (SenderState::SentEot, Event::Received(NAK) | Event::Timeout) => {
+                self.retry(Action::SendEot)
+            }
+
+            // Line noise is ignored, and the missing reply will time out and be retried
+            (SenderState::SentBlock(_) | SenderState::SentEot, Event::Received(_)) => Action::Wait,
+            (SenderState::Done, _) => Action::Wait,
+        }
+    }
+
+    /// Encodes block `block` containing `data` into `out`, returning the number of bytes to send.
+    ///
+    /// `data` is the block's slice of the log, which may be short for the last block, in which
+    /// case it is padded.
+    pub fn encode_block(&self, block: u32, data: &[u8], out: &mut [u8; MAX_PACKET_SIZE]) -> usize {
+        let data = &data[..data.len().min(BLOCK_SIZE)];
+        // Blocks are numbered from 1, wrapping after 255
+        let number = (block.wrapping_add(1) % 256) as u8;
+
+        out[0] = SOH;
+        out[1] = number;
+        out[2] = !number;
+        let payload = &mut out[3..3 + BLOCK_SIZE];
+        payload[..data.len()].copy_from_slice(data);
+        payload[data.len()..].fill(PADDING);
+
+        match self.mode {
+            Mode::Checksum => {
+                out[3 + BLOCK_SIZE] = out[3..3 + BLOCK_SIZE]
+                    .iter()
+                    .fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+                MAX_PACKET_SIZE - 1
+            }
+            Mode::Crc => {
+                let crc = crc16(&out[3..3 + BLOCK_SIZE]);
+                out[3 + BLOCK_SIZE..].copy_from_slice(&crc.to_be_bytes());
+                MAX_PACKET_SIZE
+            }
+        }
+    }
+
+    fn next_block(&mut self, block: u32) -> Action {
+        self.retries = 0;
+        if block < self.block_count {
+            self.state = SenderState::SentBlock(block);
+            Action::SendBlock(block)
+        } else {
+            self.state = SenderState::SentEot;
+            Action::SendEot
+        }
+    }
+
+    fn retry(&mut self, action: Action) -> Action {
+        self.retries += 1;
+        if self.retries >= MAX_RETRIES {
+            self.state = SenderState::Done;
+            Action::Cancel(XmodemError::TooManyRetries)
+        } else {
+            action
+        }
+    }
+}
+
+/// Computes the CRC-16 used by XMODEM, with polynomial `0x1021` and no final xor
+pub fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for byte in bytes {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        crc16, Action, Event, Mode, Sender, XmodemError, ACK, BLOCK_SIZE, CAN, CRC_START,
+        MAX_PACKET_SIZE, MAX_RETRIES, NAK,
+    };
+
+    #[test]
+    fn test_crc16() {
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+    }
+
+    #[test]
+    fn test_encode_block() {
+        let mut sender = Sender::new(BLOCK_SIZE + 3);
+        assert_eq!(sender.block_count(), 2);
+        assert_eq!(
+            sender.handle(Event::Received(CRC_START)),
+            Action::SendBlock(0)
+        );
+        assert_eq!(sender.mode(), Mode::Crc);
+
+        let mut out = [0; MAX_PACKET_SIZE];
+        assert_eq!(
+            sender.encode_block(1, &[1, 2, 3], &mut out),
+            MAX_PACKET_SIZE
+        );
+        assert_eq!(out[..6], [0x01, 2, 0xFD, 1, 2, 3]);
+        assert!(out[6..3 + BLOCK_SIZE].iter().all(|byte| *byte == 0x1A));
+        let crc = crc16(&out[3..3 + BLOCK_SIZE]);
+        assert_eq!(out[3 + BLOCK_SIZE..], crc.to_be_bytes());
+
+        // Block numbers wrap after 255
+        sender.encode_block(255, &[0; BLOCK_SIZE], &mut out);
+        assert_eq!(out[1..3], [0x00, 0xFF]);
+
+        let mut sender = Sender::new(0);
+        assert_eq!(sender.handle(Event::Received(NAK)), Action::SendEot);
+        assert_eq!(sender.mode(), Mode::Checksum);
+        let len = sender.encode_block(0, &[0x80, 0x81], &mut out);
+        assert_eq!(len, MAX_PACKET_SIZE - 1);
+        let padding = (BLOCK_SIZE as u32 - 2) * 0x1A;
+        assert_eq!(out[len - 1], ((0x80 + 0x81 + padding) % 256) as u8);
+    }
+
+    /// Runs a transfer of `data` to a receiver which always acknowledges, except that it never
+    /// receives the blocks whose index is in `dropped`. Returns the data received and the number
+    /// of blocks sent
+    fn transfer(data: &[u8], dropped: &[u32]) -> (alloc::vec::Vec<u8>, usize) {
+        let mut sender = Sender::new(data.len());
+        let mut received = alloc::vec::Vec::new();
+        let mut dropped = dropped.to_vec();
+        let mut sent = 0;
+
+        let mut action = sender.handle(Event::Timeout);
+        assert_eq!(action, Action::Wait);
+        action = sender.handle(Event::Received(CRC_START));
+        loop {
+            let event = match action {
+                Action::SendBlock(block) => {
+                    sent += 1;
+                    let start = block as usize * BLOCK_SIZE;
+                    let mut out = [0; MAX_PACKET_SIZE];
+                    let len = sender.encode_block(block, &data[start..], &mut out);
+
+                    if let Some(i) = dropped.iter().position(|dropped| *dropped == block) {
+                        dropped.remove(i);
+                        Event::Timeout
+                    } else {
+                        let payload = &out[3..len - 2];
+                        assert_eq!(out[len - 2..], crc16(payload).to_be_bytes());
+                        // A repeated block is acknowledged again but not stored twice
+                        if received.len() == start {
+                            received.extend_from_slice(payload);
+                        }
+                        Event::Received(ACK)
+                    }
+                }
+                Action::SendEot => Event::Received(ACK),
+                Action::Finished => break,
+                other => panic!("unexpected action {:?}", other),
+            };
+            action = sender.handle(event);
+        }
+
+        received.truncate(data.len());
+        (received, sent)
+    }
+
+    #[test]
+    fn test_transfer() {
+        let data: alloc::vec::Vec<u8> = (0..1000).map(|i| (i % 251) as u8).collect();
+        assert_eq!(transfer(&data, &[]), (data.clone(), 8));
+
+        // A dropped block is sent again after the receiver times out
+        assert_eq!(transfer(&data, &[2, 2, 7]), (data, 11));
+    }
+
+    #[test]
+    fn test_too_many_retries() {
+        let mut sender = Sender::new(10);
+        assert_eq!(
+            sender.handle(Event::Received(CRC_START)),
+            Action::SendBlock(0)
+        );
+        for _ in 1..MAX_RETRIES {
+            assert_eq!(sender.handle(Event::Received(NAK)), Action::SendBlock(0));
+        }
+        assert_eq!(
+            sender.handle(Event::Timeout),
+            Action::Cancel(XmodemError::TooManyRetries)
+        );
+        assert_eq!(sender.handle(Event::Received(ACK)), Action::Wait);
+
+        let mut sender = Sender::new(10);
+        sender.handle(Event::Received(CRC_START));
+        assert_eq!(
+            sender.handle(Event::Received(CAN)),
+            Action::Cancel(XmodemError::Cancelled)
+        );
+    }
+}